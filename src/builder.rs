@@ -0,0 +1,146 @@
+use crate::error::ParseError;
+use crate::legacy_parsers::{frames_from_entries, Entry, Frame, Trajectory};
+
+// Assembles a trajectory from entries added in any order, the parsers use
+// it as well so generated and loaded trajectories are grouped and checked
+// the same way.
+#[derive(Debug)]
+pub struct TrajectoryBuilder {
+    entries: Vec<Entry>,
+    fps: Option<f32>,
+    // One past the highest frame id added so far.
+    next_frame_id: i32,
+}
+
+impl Default for TrajectoryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TrajectoryBuilder {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            fps: None,
+            next_frame_id: 0,
+        }
+    }
+
+    pub fn fps(mut self, fps: f32) -> Self {
+        self.fps = Some(fps);
+        self
+    }
+
+    // Adds a frame after the last one, the agent at `positions[i]` gets
+    // id `i`. Only tests build trajectories entry by entry, the parsers
+    // use `push`.
+    #[cfg(test)]
+    pub fn add_frame(mut self, positions: &[[f32; 2]]) -> Self {
+        let frame_id = self.next_frame_id;
+        for (agent_id, position) in positions.iter().enumerate() {
+            self = self.add_entry(frame_id, agent_id as u32, *position);
+        }
+        self.next_frame_id = frame_id + 1;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn add_entry(mut self, frame_id: i32, agent_id: u32, position: [f32; 2]) -> Self {
+        self.push(Entry {
+            agent_id,
+            frame_id,
+            position,
+            time: None,
+            group: None,
+        });
+        self
+    }
+
+    pub fn push(&mut self, entry: Entry) {
+        self.next_frame_id = self.next_frame_id.max(entry.frame_id.saturating_add(1));
+        self.entries.push(entry);
+    }
+
+    // Groups the entries into frames. Agents appearing twice in a frame and
+    // positions that are not finite numbers are rejected, as are
+    // trajectories without any frame.
+    pub fn build(self) -> Result<Trajectory, ParseError> {
+        let frames = frames_from_entries(self.entries);
+        if frames.is_empty() {
+            return Err(ParseError::NoFrames);
+        }
+        for (index, frame) in frames.iter().enumerate() {
+            validate_frame(frame, index)?;
+        }
+        Ok(Trajectory::new(frames, self.fps))
+    }
+}
+
+// The checks of `TrajectoryBuilder::build` for a single frame with sorted
+// ids, for parsers that group frames themselves. Frames are reported by
+// index, the same number the timeline shows.
+pub fn validate_frame(frame: &Frame, index: usize) -> Result<(), ParseError> {
+    if let Some(pair) = frame.ids.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(ParseError::Invalid(format!(
+            "agent {} appears twice in frame {}",
+            pair[0], index
+        )));
+    }
+    let mut agents = frame.ids.iter().zip(&frame.positions);
+    if let Some((id, _)) = agents.find(|(_, p)| !p[0].is_finite() || !p[1].is_finite()) {
+        return Err(ParseError::Invalid(format!(
+            "agent {} has an invalid position in frame {}",
+            id, index
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_follow_each_other() {
+        let trajectory = TrajectoryBuilder::new()
+            .fps(4.0)
+            .add_frame(&[[0.0, 0.0], [1.0, 0.0]])
+            .add_frame(&[[0.5, 0.0]])
+            .build()
+            .unwrap();
+        assert_eq!(trajectory.fps, Some(4.0));
+        assert_eq!(trajectory.frames.len(), 2);
+        assert_eq!(trajectory.frames[0].ids, vec![0, 1]);
+        assert_eq!(trajectory.frames[1].position_of(0), Some([0.5, 0.0]));
+    }
+
+    #[test]
+    fn entries_are_grouped_like_parsed_files() {
+        let trajectory = TrajectoryBuilder::new()
+            .add_entry(3, 2, [2.0, 0.0])
+            .add_entry(1, 7, [1.0, 0.0])
+            .add_entry(3, 1, [3.0, 0.0])
+            .add_frame(&[[9.0, 9.0]])
+            .build()
+            .unwrap();
+        assert_eq!(trajectory.frames.len(), 3);
+        assert_eq!(trajectory.frames[1].ids, vec![1, 2]);
+        assert_eq!(trajectory.frames[2].position_of(0), Some([9.0, 9.0]));
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        let duplicate = TrajectoryBuilder::new()
+            .add_entry(0, 1, [0.0, 0.0])
+            .add_entry(0, 1, [1.0, 0.0])
+            .build();
+        assert!(matches!(duplicate, Err(ParseError::Invalid(_))));
+        let nan = TrajectoryBuilder::new()
+            .add_frame(&[[f32::NAN, 0.0]])
+            .build();
+        assert!(matches!(nan, Err(ParseError::Invalid(_))));
+        let empty = TrajectoryBuilder::new().build();
+        assert!(matches!(empty, Err(ParseError::NoFrames)));
+    }
+}
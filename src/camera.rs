@@ -0,0 +1,258 @@
+use std::fmt::Debug;
+use winit::event::ElementState;
+use winit::event::Event;
+use winit::event::MouseButton;
+use winit::event::MouseScrollDelta;
+use winit::event::WindowEvent;
+
+use crate::projection;
+use crate::projection::Bounds;
+
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.01;
+const MAX_ZOOM: f32 = 1000.0;
+const FOLLOW_PADDING: f32 = 0.1;
+const FOLLOW_RATE: f32 = 5.0;
+// Keyboard panning moves by this fraction of the visible area per second,
+// keyboard zoom changes the zoom by this factor per second.
+const KEY_PAN_SPEED: f32 = 0.75;
+const KEY_ZOOM_SPEED: f32 = 2.0;
+
+#[derive(Debug)]
+pub struct Camera {
+    pub center: [f32; 2],
+    pub zoom: f32,
+    // Tracks the area handed to `follow` instead of staying where it is.
+    // Any manual pan or zoom turns this off again.
+    pub follow: bool,
+    half_extent: [f32; 2],
+    viewport: [f32; 2],
+    cursor: [f32; 2],
+    hovered: bool,
+    panning: bool,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        Self {
+            center: [0.0, 0.0],
+            zoom: 1.0,
+            follow: false,
+            half_extent: [1.0, 1.0],
+            viewport: [1.0, 1.0],
+            cursor: [0.0, 0.0],
+            hovered: false,
+            panning: false,
+        }
+    }
+
+    pub fn reset(&mut self, area: (f32, f32, f32, f32)) {
+        let (left, right, bottom, top) = area;
+        self.center = [(left + right) / 2.0, (bottom + top) / 2.0];
+        self.half_extent = [(right - left) / 2.0, (top - bottom) / 2.0];
+        self.zoom = 1.0;
+    }
+
+    // Moves the view towards `area` plus some padding, approaching it
+    // exponentially so the view does not jump when the area changes.
+    pub fn follow(&mut self, area: (f32, f32, f32, f32), delta_time: f32) {
+        let (left, right, bottom, top) = area;
+        if left > right || bottom > top {
+            return;
+        }
+        let padding = FOLLOW_PADDING * f32::max(right - left, top - bottom) + 1.0;
+        let target_center = [(left + right) / 2.0, (bottom + top) / 2.0];
+        let target_half_extent = [
+            (right - left + padding) / 2.0,
+            (top - bottom + padding) / 2.0,
+        ];
+        let t = 1.0 - (-FOLLOW_RATE * delta_time).exp();
+        for i in 0..2 {
+            self.center[i] += (target_center[i] - self.center[i]) * t;
+            self.half_extent[i] += (target_half_extent[i] - self.half_extent[i]) * t;
+        }
+        self.zoom += (1.0 - self.zoom) * t;
+    }
+
+    // Moves the center towards `point` at the same rate as `follow`, the
+    // zoom stays as it is.
+    pub fn follow_point(&mut self, point: [f32; 2], delta_time: f32) {
+        self.follow = false;
+        let t = 1.0 - (-FOLLOW_RATE * delta_time).exp();
+        for (center, target) in self.center.iter_mut().zip(&point) {
+            *center += (target - *center) * t;
+        }
+    }
+
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        // A minimized window reports a zero sized viewport, keep the last
+        // usable one around so the bounds never degenerate.
+        if width > 0 && height > 0 {
+            self.viewport = [width as f32, height as f32];
+        }
+    }
+
+    // Bounds are widened along one axis so that one world unit covers the
+    // same number of pixels horizontally and vertically.
+    pub fn bounds(&self) -> Bounds {
+        let half_width = self.half_extent[0] / self.zoom;
+        let half_height = self.half_extent[1] / self.zoom;
+        projection::fit_aspect_ratio(
+            (
+                self.center[0] - half_width,
+                self.center[0] + half_width,
+                self.center[1] - half_height,
+                self.center[1] + half_height,
+            ),
+            self.viewport[0] / self.viewport[1],
+        )
+    }
+
+    pub fn screen_to_world(&self, screen: [f32; 2]) -> [f32; 2] {
+        projection::screen_to_world(screen, self.bounds(), self.viewport)
+    }
+
+    pub fn world_to_screen(&self, world: [f32; 2]) -> [f32; 2] {
+        projection::world_to_screen(world, self.bounds(), self.viewport)
+    }
+
+    // World position under the mouse, `None` while the cursor is outside
+    // of the window.
+    pub fn cursor_world(&self) -> Option<[f32; 2]> {
+        if self.hovered {
+            Some(self.screen_to_world(self.cursor))
+        } else {
+            None
+        }
+    }
+
+    pub fn handle_event<T>(&mut self, evt: &Event<T>)
+    where
+        T: Debug,
+    {
+        let event = match evt {
+            Event::WindowEvent { event, .. } => event,
+            _ => return,
+        };
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = [position.x as f32, position.y as f32];
+                if self.panning {
+                    self.follow = false;
+                    let (left, right, bottom, top) = self.bounds();
+                    let dx = (cursor[0] - self.cursor[0]) / self.viewport[0] * (right - left);
+                    let dy = (cursor[1] - self.cursor[1]) / self.viewport[1] * (top - bottom);
+                    self.center[0] -= dx;
+                    self.center[1] += dy;
+                }
+                self.cursor = cursor;
+                self.hovered = true;
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.hovered = false;
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Middle,
+                ..
+            } => {
+                self.panning = *state == ElementState::Pressed;
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+                self.follow = false;
+                self.zoom_at(self.cursor, ZOOM_STEP.powf(steps));
+            }
+            _ => {}
+        }
+    }
+
+    // Pans in `direction`, with components in [-1, 1] and positive y
+    // pointing up, and zooms in for positive `zoom` and out for negative.
+    // Both are scaled with the visible area so they feel the same at every
+    // zoom level.
+    pub fn move_by_keys(&mut self, direction: [f32; 2], zoom: f32, delta_time: f32) {
+        if direction == [0.0, 0.0] && zoom == 0.0 {
+            return;
+        }
+        self.follow = false;
+        let (left, right, bottom, top) = self.bounds();
+        let step = KEY_PAN_SPEED * delta_time;
+        self.center[0] += direction[0] * (right - left) * step;
+        self.center[1] += direction[1] * (top - bottom) * step;
+        let center = self.world_to_screen(self.center);
+        self.zoom_at(center, KEY_ZOOM_SPEED.powf(zoom * delta_time));
+    }
+
+    fn zoom_at(&mut self, screen: [f32; 2], factor: f32) {
+        let before = self.screen_to_world(screen);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let after = self.screen_to_world(screen);
+        self.center[0] += before[0] - after[0];
+        self.center[1] += before[1] - after[1];
+    }
+}
+
+// Grows `area` by `factor` of its larger dimension plus `margin` on every
+// side, so agents on the boundary are fully visible.
+pub fn pad_area(area: (f32, f32, f32, f32), factor: f32, margin: f32) -> (f32, f32, f32, f32) {
+    let (left, right, bottom, top) = area;
+    let padding = factor.max(0.0) * f32::max(right - left, top - bottom) + margin;
+    (
+        left - padding,
+        right + padding,
+        bottom - padding,
+        top + padding,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_panning_scales_with_zoom() {
+        let mut camera = Camera::new();
+        camera.reset((0.0, 10.0, 0.0, 10.0));
+        camera.move_by_keys([1.0, 0.0], 0.0, 1.0);
+        assert!((camera.center[0] - 12.5).abs() < 1e-4);
+        camera.zoom = 10.0;
+        camera.move_by_keys([0.0, -1.0], 0.0, 1.0);
+        assert!((camera.center[1] - 4.25).abs() < 1e-4);
+        camera.move_by_keys([0.0, 0.0], 1.0, 0.5);
+        assert!((camera.zoom - 10.0 * 2f32.sqrt()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn following_a_point_converges() {
+        let mut camera = Camera::new();
+        camera.zoom = 3.0;
+        for _ in 0..100 {
+            camera.follow_point([4.0, -2.0], 0.1);
+        }
+        assert!((camera.center[0] - 4.0).abs() < 1e-3);
+        assert!((camera.center[1] + 2.0).abs() < 1e-3);
+        assert_eq!(camera.zoom, 3.0);
+    }
+
+    #[test]
+    fn padding_scales_with_larger_dimension() {
+        assert_eq!(
+            pad_area((0.0, 10.0, 0.0, 4.0), 0.05, 0.25),
+            (-0.75, 10.75, -0.75, 4.75)
+        );
+        assert_eq!(
+            pad_area((1.0, 1.0, 2.0, 2.0), 0.05, 0.0),
+            (1.0, 1.0, 2.0, 2.0)
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[clap(name = "vistwo", version, about = "Viewer for pedestrian trajectories")]
+pub struct Args {
+    /// Trajectory to open at startup, `-` reads the txt format from stdin
+    pub trajectory: Option<PathBuf>,
+    /// Geometry to show, a WKT file of the walkable area, a jpscore xml file
+    /// or a JuPedSim database
+    #[clap(long, value_name = "FILE")]
+    pub geometry: Option<PathBuf>,
+    /// Renders this frame to an image and exits without opening a window,
+    /// can be given several times
+    #[clap(long = "render-frame", value_name = "FRAME", requires = "trajectory")]
+    pub render_frames: Vec<usize>,
+    /// Times loading the trajectory and rendering every frame offscreen,
+    /// prints a json summary and exits
+    #[clap(long, requires = "trajectory", conflicts_with = "render-frames")]
+    pub bench: bool,
+    /// Image to render to, the frame number is appended when rendering
+    /// several frames
+    #[clap(long, value_name = "PNG", default_value = "frame.png")]
+    pub out: PathBuf,
+    /// Width of rendered images in pixels
+    #[clap(long, default_value_t = 1920)]
+    pub width: u32,
+    /// Height of rendered images in pixels
+    #[clap(long, default_value_t = 1080)]
+    pub height: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trajectory_is_optional() {
+        let args = Args::try_parse_from(["vistwo"]).unwrap();
+        assert_eq!(args.trajectory, None);
+        let args = Args::try_parse_from(["vistwo", "-"]).unwrap();
+        assert_eq!(args.trajectory, Some(PathBuf::from("-")));
+        assert!(Args::try_parse_from(["vistwo", "a.txt", "b.txt"]).is_err());
+    }
+
+    #[test]
+    fn render_flags_need_a_trajectory() {
+        let args = Args::try_parse_from([
+            "vistwo",
+            "traj.txt",
+            "--render-frame",
+            "120",
+            "--render-frame",
+            "130",
+            "--out",
+            "shot.png",
+            "--width",
+            "800",
+        ])
+        .unwrap();
+        assert_eq!(args.render_frames, vec![120, 130]);
+        assert_eq!(args.out, PathBuf::from("shot.png"));
+        assert_eq!((args.width, args.height), (800, 1080));
+        assert!(Args::try_parse_from(["vistwo", "--render-frame", "1"]).is_err());
+    }
+
+    #[test]
+    fn geometry_is_optional() {
+        let args = Args::try_parse_from(["vistwo", "traj.txt"]).unwrap();
+        assert_eq!(args.geometry, None);
+        let args = Args::try_parse_from(["vistwo", "--geometry", "area.wkt"]).unwrap();
+        assert_eq!(args.geometry, Some(PathBuf::from("area.wkt")));
+        assert_eq!(args.trajectory, None);
+    }
+
+    #[test]
+    fn bench_excludes_rendering_frames() {
+        let args = Args::try_parse_from(["vistwo", "traj.txt", "--bench"]).unwrap();
+        assert!(args.bench);
+        assert!(Args::try_parse_from(["vistwo", "--bench"]).is_err());
+        assert!(
+            Args::try_parse_from(["vistwo", "traj.txt", "--bench", "--render-frame", "1"]).is_err()
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Colormap {
+    Viridis,
+    Jet,
+    Grayscale,
+    CoolWarm,
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 4] = [
+        Colormap::Viridis,
+        Colormap::Jet,
+        Colormap::Grayscale,
+        Colormap::CoolWarm,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Colormap::Viridis => "Viridis",
+            Colormap::Jet => "Jet",
+            Colormap::Grayscale => "Grayscale",
+            Colormap::CoolWarm => "CoolWarm",
+        }
+    }
+
+    // Looks up a colormap by its name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Colormap> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|map| map.name().eq_ignore_ascii_case(name))
+    }
+
+    // Control points sorted by position, colors in between are
+    // interpolated linearly. The viridis samples happen to contain 0.318,
+    // which clippy mistakes for 1/pi.
+    #[allow(clippy::approx_constant)]
+    fn control_points(self) -> &'static [(f32, [f32; 3])] {
+        match self {
+            Colormap::Viridis => &[
+                (0.0, [0.267, 0.005, 0.329]),
+                (0.1, [0.283, 0.141, 0.458]),
+                (0.2, [0.254, 0.265, 0.530]),
+                (0.3, [0.207, 0.372, 0.553]),
+                (0.4, [0.164, 0.471, 0.558]),
+                (0.5, [0.128, 0.567, 0.551]),
+                (0.6, [0.135, 0.659, 0.518]),
+                (0.7, [0.267, 0.749, 0.441]),
+                (0.8, [0.478, 0.821, 0.318]),
+                (0.9, [0.741, 0.873, 0.150]),
+                (1.0, [0.993, 0.906, 0.144]),
+            ],
+            Colormap::Jet => &[
+                (0.0, [0.0, 0.0, 0.5]),
+                (0.125, [0.0, 0.0, 1.0]),
+                (0.375, [0.0, 1.0, 1.0]),
+                (0.625, [1.0, 1.0, 0.0]),
+                (0.875, [1.0, 0.0, 0.0]),
+                (1.0, [0.5, 0.0, 0.0]),
+            ],
+            Colormap::Grayscale => &[(0.0, [0.0, 0.0, 0.0]), (1.0, [1.0, 1.0, 1.0])],
+            Colormap::CoolWarm => &[
+                (0.0, [0.230, 0.299, 0.754]),
+                (0.5, [0.865, 0.865, 0.865]),
+                (1.0, [0.706, 0.016, 0.150]),
+            ],
+        }
+    }
+}
+
+// Maps `t` to a color of `map`, values outside of 0..1 are clamped.
+pub fn sample(map: Colormap, t: f32) -> [f32; 3] {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    let points = map.control_points();
+    for pair in points.windows(2) {
+        let (from, from_color) = pair[0];
+        let (to, to_color) = pair[1];
+        if t <= to {
+            let s = (t - from) / (to - from);
+            let mut color = [0.0; 3];
+            for (c, (a, b)) in color.iter_mut().zip(from_color.iter().zip(&to_color)) {
+                *c = a + (b - a) * s;
+            }
+            return color;
+        }
+    }
+    points[points.len() - 1].1
+}
+
+// Samples `map` at `size` evenly spaced positions, used to upload the map
+// as a lookup texture for the shaders.
+pub fn lookup_table(map: Colormap, size: usize) -> Vec<(f32, f32, f32)> {
+    (0..size)
+        .map(|i| {
+            let [r, g, b] = sample(map, i as f32 / (size - 1).max(1) as f32);
+            (r, g, b)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn viridis() {
+        assert_color(sample(Colormap::Viridis, 0.0), [0.267, 0.005, 0.329]);
+        assert_color(sample(Colormap::Viridis, 0.5), [0.128, 0.567, 0.551]);
+        assert_color(sample(Colormap::Viridis, 1.0), [0.993, 0.906, 0.144]);
+    }
+
+    #[test]
+    fn jet() {
+        assert_color(sample(Colormap::Jet, 0.0), [0.0, 0.0, 0.5]);
+        assert_color(sample(Colormap::Jet, 0.5), [0.5, 1.0, 0.5]);
+        assert_color(sample(Colormap::Jet, 1.0), [0.5, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn grayscale() {
+        assert_color(sample(Colormap::Grayscale, 0.0), [0.0, 0.0, 0.0]);
+        assert_color(sample(Colormap::Grayscale, 0.5), [0.5, 0.5, 0.5]);
+        assert_color(sample(Colormap::Grayscale, 1.0), [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn cool_warm() {
+        assert_color(sample(Colormap::CoolWarm, 0.0), [0.230, 0.299, 0.754]);
+        assert_color(sample(Colormap::CoolWarm, 0.5), [0.865, 0.865, 0.865]);
+        assert_color(sample(Colormap::CoolWarm, 1.0), [0.706, 0.016, 0.150]);
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        assert_eq!(Colormap::from_name("viridis"), Some(Colormap::Viridis));
+        assert_eq!(Colormap::from_name("COOLWARM"), Some(Colormap::CoolWarm));
+        assert_eq!(Colormap::from_name("magma"), None);
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        assert_color(sample(Colormap::Jet, -1.0), sample(Colormap::Jet, 0.0));
+        assert_color(sample(Colormap::Jet, 2.0), sample(Colormap::Jet, 1.0));
+    }
+}
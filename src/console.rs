@@ -1,14 +1,128 @@
 use imgui::ChildWindow;
 use imgui::Condition;
+use imgui::HistoryDirection;
+use imgui::InputTextCallback;
+use imgui::InputTextCallbackHandler;
 use imgui::InputTextFlags;
+use imgui::TextCallbackData;
 use imgui::Ui;
 use imgui::Window;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::colormap::Colormap;
+use crate::export;
+use crate::replay;
+use crate::validate;
+use crate::ApplicationState;
+
+pub type Command = fn(&[&str], &mut ApplicationState) -> Result<String, String>;
+
+pub struct CommandRegistry {
+    commands: HashMap<String, Command>,
+}
+
+impl fmt::Debug for CommandRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.commands.keys().collect();
+        names.sort();
+        f.debug_struct("CommandRegistry")
+            .field("commands", &names)
+            .finish()
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self {
+            commands: HashMap::new(),
+        }
+    }
+
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.register("open", cmd_open);
+        registry.register("reload", cmd_reload);
+        registry.register("play", cmd_play);
+        registry.register("pause", cmd_pause);
+        registry.register("seek", cmd_seek);
+        registry.register("clear", cmd_clear);
+        registry.register("export", cmd_export);
+        registry.register("export_json", cmd_export_json);
+        registry.register("stats", cmd_stats);
+        registry.register("next_hotspot", cmd_next_hotspot);
+        registry.register("prev_hotspot", cmd_prev_hotspot);
+        registry.register("copy", cmd_copy);
+        registry.register("validate", cmd_validate);
+        registry.register("view", cmd_view);
+        registry.register("radius", cmd_radius);
+        registry.register("colormap", cmd_colormap);
+        registry.register("bg", cmd_bg);
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, command: Command) {
+        self.commands.insert(name.to_string(), command);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Command> {
+        self.commands.get(name).copied()
+    }
+}
+
+// Recalls previously submitted commands when Up/Down is pressed while the
+// input field is focused. The line being edited before the recall started is
+// kept in `pending` so stepping past the newest entry restores it.
+struct HistoryRecall<'a> {
+    command_history: &'a [String],
+    cursor: &'a mut usize,
+    pending: &'a mut String,
+}
+
+impl InputTextCallbackHandler for HistoryRecall<'_> {
+    fn on_history(&mut self, direction: HistoryDirection, mut data: TextCallbackData) {
+        let newest = self.command_history.len();
+        if *self.cursor == newest {
+            *self.pending = data.str().to_string();
+        }
+        match direction {
+            HistoryDirection::Up if *self.cursor > 0 => *self.cursor -= 1,
+            HistoryDirection::Down if *self.cursor < newest => *self.cursor += 1,
+            _ => return,
+        }
+        let line = if *self.cursor == newest {
+            self.pending.as_str()
+        } else {
+            self.command_history[*self.cursor].as_str()
+        };
+        data.clear();
+        data.push_str(line);
+    }
+}
 
 #[derive(Debug)]
 pub struct Console {
     input: String,
     history: Vec<String>,
+    command_history: Vec<String>,
+    history_cursor: usize,
+    pending_input: String,
     refocus: bool,
+    commands: CommandRegistry,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Console {
@@ -16,40 +130,338 @@ impl Console {
         Self {
             input: String::with_capacity(128),
             history: Vec::new(),
+            command_history: Vec::new(),
+            history_cursor: 0,
+            pending_input: String::new(),
             refocus: true,
+            commands: CommandRegistry::with_builtins(),
         }
     }
 
-    pub fn draw(&mut self, ui: &Ui) {
-        if let Some(window) = Window::new("Console")
-            .size([800.0, 300.0], Condition::Always)
+    pub fn print(&mut self, line: &str) {
+        self.history.push(line.to_string());
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    // Everything printed so far, oldest first.
+    pub fn lines(&self) -> &[String] {
+        &self.history
+    }
+
+    // Takes the whole application state instead of `&mut self` so that
+    // commands are able to modify the state the console is part of.
+    pub fn draw(ui: &Ui, state: &mut ApplicationState) {
+        if let Some(_window) = Window::new("Console")
+            .size([800.0, 300.0], Condition::FirstUseEver)
             .collapsible(false)
             .begin(ui)
         {
-            if let Some(child) = ChildWindow::new("console_history")
+            let console = &mut state.console;
+            if let Some(_child) = ChildWindow::new("console_history")
                 .size([0.0, -24.0])
                 .border(true)
                 .begin(ui)
             {
-                for line in &self.history {
-                    ui.text(&line);
+                for line in &console.history {
+                    ui.text(line);
                 }
             }
-            if self.refocus {
+            if console.refocus {
                 ui.set_keyboard_focus_here();
             }
             if ui
-                .input_text("Run Command", &mut self.input)
+                .input_text("Run Command", &mut console.input)
                 .flags(InputTextFlags::ENTER_RETURNS_TRUE | InputTextFlags::ALWAYS_OVERWRITE)
                 .hint("Your command...")
+                .callback(
+                    InputTextCallback::HISTORY,
+                    HistoryRecall {
+                        command_history: &console.command_history,
+                        cursor: &mut console.history_cursor,
+                        pending: &mut console.pending_input,
+                    },
+                )
                 .build()
             {
-                self.history.push(self.input.clone());
-                self.input.clear();
-                self.refocus = true;
+                let input = std::mem::take(&mut console.input);
+                if !input.trim().is_empty() {
+                    console.command_history.push(input.clone());
+                }
+                console.history_cursor = console.command_history.len();
+                console.pending_input.clear();
+                console.refocus = true;
+                Self::execute(&input, state);
             } else {
-                self.refocus = false;
+                console.refocus = false;
             }
         }
     }
+
+    pub fn execute(input: &str, state: &mut ApplicationState) {
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return,
+        };
+        state.console.print(&format!("> {}", input));
+        let result = match state.console.commands.get(name) {
+            Some(command) => command(args, state),
+            None => Err(format!("Unknown command '{}'", name)),
+        };
+        match result {
+            Ok(output) => {
+                for line in output.lines() {
+                    state.console.print(line);
+                }
+            }
+            Err(error) => state.console.print(&format!("error: {}", error)),
+        }
+    }
+}
+
+fn cmd_open(args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let path = match args {
+        [path] => Path::new(path),
+        _ => return Err("usage: open <path>".to_string()),
+    };
+    if !path.is_file() {
+        return Err(format!("No such file '{}'", path.display()));
+    }
+    state.open_trajectory(path);
+    Ok(String::new())
+}
+
+fn cmd_reload(_args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    state.reload()?;
+    Ok(String::new())
+}
+
+fn cmd_play(_args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let replay = state.replay_mut().ok_or("No trajectory loaded")?;
+    replay.play();
+    Ok(String::new())
+}
+
+fn cmd_pause(_args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let replay = state.replay_mut().ok_or("No trajectory loaded")?;
+    replay.pause();
+    Ok(String::new())
+}
+
+fn cmd_seek(args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let frame_index = match args {
+        [frame] => frame
+            .parse::<usize>()
+            .map_err(|_| format!("'{}' is not a valid frame index", frame))?,
+        _ => return Err("usage: seek <frame>".to_string()),
+    };
+    let replay = state.replay_mut().ok_or("No trajectory loaded")?;
+    replay.seek(frame_index);
+    Ok(format!("At frame {}", replay.current_frame_index))
+}
+
+fn cmd_clear(_args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    state.console.clear();
+    Ok(String::new())
+}
+
+fn cmd_stats(_args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let replay = match state.replay() {
+        Some(replay) => replay,
+        None => return Ok("No trajectory loaded".to_string()),
+    };
+    let counts: Vec<usize> = (0..replay.frames())
+        .map(|i| replay.frame(i).ids.len())
+        .collect();
+    let min = counts.iter().copied().min().unwrap_or(0);
+    let max = counts.iter().copied().max().unwrap_or(0);
+    let mean = counts.iter().sum::<usize>() as f32 / counts.len().max(1) as f32;
+    let (left, right, bottom, top) = replay.area();
+    Ok(format!(
+        "frames:   {}\n\
+         agents:   min {} / max {} / mean {:.1} per frame\n\
+         area:     x: [{:.2}, {:.2}] y: [{:.2}, {:.2}]\n\
+         fps:      {:.2}\n\
+         duration: {}",
+        replay.frames(),
+        min,
+        max,
+        mean,
+        left,
+        right,
+        bottom,
+        top,
+        1.0 / replay.frame_duration().as_secs_f32(),
+        replay::format_timecode(replay.total_time())
+    ))
+}
+
+fn cmd_next_hotspot(_args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let threshold = state.settings.hotspot_threshold;
+    let replay = state.replay_mut().ok_or("No trajectory loaded")?;
+    match replay.next_hotspot(threshold) {
+        Some(frame) => Ok(format!("At frame {}", frame)),
+        None => Ok("No further hotspot".to_string()),
+    }
+}
+
+fn cmd_prev_hotspot(_args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let threshold = state.settings.hotspot_threshold;
+    let replay = state.replay_mut().ok_or("No trajectory loaded")?;
+    match replay.prev_hotspot(threshold) {
+        Some(frame) => Ok(format!("At frame {}", frame)),
+        None => Ok("No earlier hotspot".to_string()),
+    }
+}
+
+fn cmd_copy(_args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let replay = state.replay().ok_or("No trajectory loaded")?;
+    let rows = export::copy_frame_to_clipboard(replay.current_frame())?;
+    Ok(format!(
+        "Copied {} rows of frame {} to the clipboard",
+        rows, replay.current_frame_index
+    ))
+}
+
+fn cmd_validate(args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    // Long listings would bury everything else in the console.
+    const MAX_LISTED: usize = 50;
+    let max_speed = match args {
+        [] => state.settings.teleport_speed,
+        [speed] => speed
+            .parse::<f32>()
+            .map_err(|_| format!("'{}' is not a valid speed", speed))?,
+        _ => return Err("usage: validate [max_speed]".to_string()),
+    };
+    let replay = state.replay().ok_or("No trajectory loaded")?;
+    let teleports = validate::find_teleports(replay, max_speed);
+    if teleports.is_empty() {
+        return Ok(format!("No agent moves faster than {:.2} m/s", max_speed));
+    }
+    let mut lines = vec![format!(
+        "{} steps faster than {:.2} m/s:",
+        teleports.len(),
+        max_speed
+    )];
+    for teleport in teleports.iter().take(MAX_LISTED) {
+        lines.push(format!(
+            "  agent {} at frame {}: {:.2} m/s",
+            teleport.id, teleport.frame, teleport.speed
+        ));
+    }
+    if teleports.len() > MAX_LISTED {
+        lines.push(format!("  ... and {} more", teleports.len() - MAX_LISTED));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn cmd_view(args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let values = args
+        .iter()
+        .map(|arg| {
+            arg.parse::<f32>()
+                .map_err(|_| format!("'{}' is not a valid coordinate", arg))
+        })
+        .collect::<Result<Vec<f32>, String>>()?;
+    match values[..] {
+        [] => {}
+        [left, right, bottom, top] => {
+            if left >= right || bottom >= top {
+                return Err("left must be below right and bottom below top".to_string());
+            }
+            state.lock_view((left, right, bottom, top));
+        }
+        _ => return Err("usage: view [<left> <right> <bottom> <top>]".to_string()),
+    }
+    let (left, right, bottom, top) = state.camera.bounds();
+    Ok(format!(
+        "view {} {} {} {}{}",
+        left,
+        right,
+        bottom,
+        top,
+        if state.view_locked { " (locked)" } else { "" }
+    ))
+}
+
+fn cmd_radius(args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    match args {
+        [] => {}
+        [radius] => {
+            let radius = radius
+                .parse::<f32>()
+                .ok()
+                .filter(|r| r.is_finite() && *r > 0.0)
+                .ok_or_else(|| format!("'{}' is not a valid radius", radius))?;
+            state.settings.agent_radius = radius;
+            state.settings.auto_agent_radius = false;
+        }
+        _ => return Err("usage: radius [<meters>]".to_string()),
+    }
+    Ok(format!("radius {}", state.settings.agent_radius))
+}
+
+fn cmd_colormap(args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    match args {
+        [] => {}
+        [name] => {
+            state.settings.speed_colormap = Colormap::from_name(name).ok_or_else(|| {
+                let names: Vec<&str> = Colormap::ALL.iter().map(|map| map.name()).collect();
+                format!(
+                    "Unknown colormap '{}', expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            })?;
+        }
+        _ => return Err("usage: colormap [<name>]".to_string()),
+    }
+    Ok(format!("colormap {}", state.settings.speed_colormap.name()))
+}
+
+fn cmd_bg(args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let values = args
+        .iter()
+        .map(|arg| {
+            arg.parse::<f32>()
+                .ok()
+                .filter(|v| (0.0..=1.0).contains(v))
+                .ok_or_else(|| format!("'{}' is not a color component between 0 and 1", arg))
+        })
+        .collect::<Result<Vec<f32>, String>>()?;
+    match values[..] {
+        [] => {}
+        [r, g, b] => state.settings.background = [r, g, b, 1.0],
+        _ => return Err("usage: bg [<r> <g> <b>]".to_string()),
+    }
+    let [r, g, b, _] = state.settings.background;
+    Ok(format!("bg {} {} {}", r, g, b))
+}
+
+fn cmd_export_json(args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let path = match args {
+        [path] => Path::new(path),
+        _ => return Err("usage: export_json <path>".to_string()),
+    };
+    state.export_json(path)
+}
+
+fn cmd_export(args: &[&str], state: &mut ApplicationState) -> Result<String, String> {
+    let (directory, fps) = match args {
+        [directory] => {
+            let replay = state.replay().ok_or("No trajectory loaded")?;
+            (directory, 1.0 / replay.frame_duration().as_secs_f32())
+        }
+        [directory, fps] => (
+            directory,
+            fps.parse::<f32>()
+                .map_err(|_| format!("'{}' is not a valid frame rate", fps))?,
+        ),
+        _ => return Err("usage: export <directory> [fps]".to_string()),
+    };
+    state.start_export(PathBuf::from(directory), fps)?;
+    Ok(String::new())
 }
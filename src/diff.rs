@@ -0,0 +1,110 @@
+use crate::layers;
+use crate::layers::LoadedTrajectory;
+use crate::legacy_parsers::Frame;
+
+// Where the same agent id is in two trajectories at the same time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Displacement {
+    pub id: u32,
+    pub from: [f32; 2],
+    pub to: [f32; 2],
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divergence {
+    pub mean: f32,
+    pub max: f32,
+    pub agents: usize,
+}
+
+// Pairs up the agents present in both frames, relying on `ids` being sorted.
+pub fn displacements(a: &Frame, b: &Frame) -> Vec<Displacement> {
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.ids.len() && j < b.ids.len() {
+        match a.ids[i].cmp(&b.ids[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                let from = a.positions[i];
+                let to = b.positions[j];
+                result.push(Displacement {
+                    id: a.ids[i],
+                    from,
+                    to,
+                    distance: ((to[0] - from[0]).powi(2) + (to[1] - from[1]).powi(2)).sqrt(),
+                });
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result
+}
+
+// Displacements between the frames the two layers show while the first
+// layer is at `frame_index`, `None` unless both layers exist and differ.
+pub fn layer_displacements(
+    layers: &[LoadedTrajectory],
+    pair: (usize, usize),
+    frame_index: usize,
+) -> Option<Vec<Displacement>> {
+    let (a, b) = pair;
+    if a == b || a >= layers.len() || b >= layers.len() {
+        return None;
+    }
+    let frame_a = layers[a]
+        .replay
+        .frame(layers::frame_index(layers, a, frame_index));
+    let frame_b = layers[b]
+        .replay
+        .frame(layers::frame_index(layers, b, frame_index));
+    Some(displacements(frame_a, frame_b))
+}
+
+pub fn divergence(displacements: &[Displacement]) -> Option<Divergence> {
+    if displacements.is_empty() {
+        return None;
+    }
+    let sum: f32 = displacements.iter().map(|d| d.distance).sum();
+    let max = displacements.iter().map(|d| d.distance).fold(0.0, f32::max);
+    Some(Divergence {
+        mean: sum / displacements.len() as f32,
+        max,
+        agents: displacements.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(ids: Vec<u32>, positions: Vec<[f32; 2]>) -> Frame {
+        Frame {
+            ids,
+            positions,
+            time: None,
+            groups: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn only_shared_ids_are_paired() {
+        let a = frame(vec![1, 2, 4], vec![[0.0, 0.0], [1.0, 1.0], [2.0, 2.0]]);
+        let b = frame(vec![2, 3, 4], vec![[4.0, 5.0], [0.0, 0.0], [2.0, 2.0]]);
+        let pairs = displacements(&a, &b);
+        let ids: Vec<u32> = pairs.iter().map(|d| d.id).collect();
+        assert_eq!(ids, vec![2, 4]);
+        assert_eq!(pairs[0].distance, 5.0);
+        assert_eq!(
+            divergence(&pairs),
+            Some(Divergence {
+                mean: 2.5,
+                max: 5.0,
+                agents: 2
+            })
+        );
+        assert_eq!(divergence(&[]), None);
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ParseError {
+    Io(std::io::Error),
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    // Well-formed input that describes something impossible, like an agent
+    // appearing twice in a frame, where no line number applies.
+    Invalid(String),
+    Unsupported(String),
+    NoFrames,
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+    #[cfg(feature = "hdf5")]
+    Hdf5(hdf5::Error),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "{}", e),
+            ParseError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            ParseError::Invalid(message) => write!(f, "{}", message),
+            ParseError::Unsupported(message) => write!(f, "{}", message),
+            ParseError::NoFrames => write!(f, "no frames found"),
+            #[cfg(feature = "sqlite")]
+            ParseError::Sqlite(e) => write!(f, "{}", e),
+            #[cfg(feature = "hdf5")]
+            ParseError::Hdf5(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            ParseError::Syntax { .. }
+            | ParseError::Invalid(_)
+            | ParseError::Unsupported(_)
+            | ParseError::NoFrames => None,
+            #[cfg(feature = "sqlite")]
+            ParseError::Sqlite(e) => Some(e),
+            #[cfg(feature = "hdf5")]
+            ParseError::Hdf5(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for ParseError {
+    fn from(e: std::io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+impl From<csv::Error> for ParseError {
+    fn from(e: csv::Error) -> Self {
+        let line = e.position().map_or(0, |p| p.line() as usize);
+        let message = e.to_string();
+        match e.into_kind() {
+            csv::ErrorKind::Io(e) => ParseError::Io(e),
+            _ => ParseError::Syntax { line, message },
+        }
+    }
+}
+
+impl From<serde_json::Error> for ParseError {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            ParseError::Io(e.into())
+        } else {
+            ParseError::Syntax {
+                line: e.line(),
+                message: e.to_string(),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for ParseError {
+    fn from(e: rusqlite::Error) -> Self {
+        ParseError::Sqlite(e)
+    }
+}
+
+#[cfg(feature = "hdf5")]
+impl From<hdf5::Error> for ParseError {
+    fn from(e: hdf5::Error) -> Self {
+        ParseError::Hdf5(e)
+    }
+}
+
+// Failures while setting up the window and the OpenGL resources, usually
+// caused by a driver that lacks the required OpenGL version.
+#[derive(Debug)]
+pub enum InitError {
+    Context(glium::glutin::CreationError),
+    Display(glium::backend::glutin::DisplayCreationError),
+    Renderer(imgui_glium_renderer::RendererError),
+    Program(glium::ProgramCreationError),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitError::Context(e) => write!(f, "cannot create an OpenGL context: {}", e),
+            InitError::Display(e) => write!(f, "cannot open the window: {}", e),
+            InitError::Renderer(e) => write!(f, "cannot initialize the UI renderer: {}", e),
+            InitError::Program(e) => write!(f, "cannot compile the shaders: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for InitError {}
+
+impl From<glium::backend::glutin::DisplayCreationError> for InitError {
+    fn from(e: glium::backend::glutin::DisplayCreationError) -> Self {
+        match e {
+            glium::backend::glutin::DisplayCreationError::GlutinCreationError(e) => {
+                InitError::Context(e)
+            }
+            e => InitError::Display(e),
+        }
+    }
+}
+
+impl From<imgui_glium_renderer::RendererError> for InitError {
+    fn from(e: imgui_glium_renderer::RendererError) -> Self {
+        InitError::Renderer(e)
+    }
+}
+
+impl From<glium::ProgramCreationError> for InitError {
+    fn from(e: glium::ProgramCreationError) -> Self {
+        InitError::Program(e)
+    }
+}
@@ -0,0 +1,182 @@
+//! Renders a replay into a numbered PNG sequence (`frame_0000.png`, ...).
+//!
+//! The images can be stitched into a video with ffmpeg, e.g. for an export
+//! at 25 fps:
+//!
+//! ```text
+//! ffmpeg -framerate 25 -i frame_%04d.png -c:v libx264 -pix_fmt yuv420p replay.mp4
+//! ```
+
+use glium::texture::RawImage2d;
+use glium::texture::Texture2d;
+use serde::Serialize;
+use std::io::BufWriter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::legacy_parsers::Frame;
+use crate::legacy_parsers::Trajectory;
+use crate::replay::Replay;
+
+#[derive(Debug)]
+pub struct AnimationExport {
+    pub directory: PathBuf,
+    pub fps: f32,
+    next_image: usize,
+    image_count: usize,
+    first_frame: usize,
+    last_frame: usize,
+}
+
+impl AnimationExport {
+    // Exports the clip of `replay` as it is set when the export starts.
+    pub fn new(directory: PathBuf, fps: f32, replay: &Replay) -> Self {
+        let (first_frame, last_frame) = replay.clip();
+        let duration = replay.time_of(last_frame) - replay.time_of(first_frame);
+        let image_count = (duration.as_secs_f32() * fps) as usize + 1;
+        Self {
+            directory,
+            fps,
+            next_image: 0,
+            image_count,
+            first_frame,
+            last_frame,
+        }
+    }
+
+    // Index of the next image to write together with the trajectory frame
+    // that is visible at that point in time, relative to the clip start.
+    pub fn next(&mut self, replay: &Replay) -> Option<(usize, usize)> {
+        if self.is_finished() {
+            return None;
+        }
+        let image_index = self.next_image;
+        self.next_image += 1;
+        let time = Duration::from_secs_f64(image_index as f64 / self.fps as f64);
+        let frame_index = replay.frame_at(replay.time_of(self.first_frame) + time);
+        Some((image_index, frame_index.min(self.last_frame)))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_image >= self.image_count
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.next_image as f32 / self.image_count as f32
+    }
+
+    pub fn image_count(&self) -> usize {
+        self.image_count
+    }
+
+    pub fn image_path(&self, image_index: usize) -> PathBuf {
+        self.directory.join(format!("frame_{:04}.png", image_index))
+    }
+
+    pub fn ffmpeg_command(&self) -> String {
+        format!(
+            "ffmpeg -framerate {} -i {} -c:v libx264 -pix_fmt yuv420p replay.mp4",
+            self.fps,
+            self.directory.join("frame_%04d.png").display()
+        )
+    }
+}
+
+// Positions of `frame` as csv with an `id,x,y` header, one agent per line.
+pub fn frame_csv(frame: &Frame) -> String {
+    let mut csv = String::from("id,x,y\n");
+    for (id, p) in frame.ids.iter().zip(&frame.positions) {
+        csv.push_str(&format!("{},{},{}\n", id, p[0], p[1]));
+    }
+    csv
+}
+
+// Copies the positions of `frame` to the system clipboard, returns the
+// number of agents copied.
+pub fn copy_frame_to_clipboard(frame: &Frame) -> Result<usize, String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| format!("Cannot access the clipboard: {}", e))?;
+    clipboard
+        .set_text(frame_csv(frame))
+        .map_err(|e| format!("Cannot copy to the clipboard: {}", e))?;
+    Ok(frame.ids.len())
+}
+
+// Read back by `parsers::parse_trajectory_json`.
+#[derive(Serialize)]
+struct JsonTrajectory<'a> {
+    fps: Option<f32>,
+    // (left, right, bottom, top) of all positions.
+    bounds: (f32, f32, f32, f32),
+    geometry: Option<&'a Path>,
+    units: Option<&'a str>,
+    frames: &'a [Frame],
+}
+
+// Writes `trajectory` as json, independent of the format it was read from.
+pub fn save_trajectory_json(trajectory: &Trajectory, path: &Path) -> Result<(), String> {
+    let error = |e: &dyn std::fmt::Display| format!("Cannot write '{}': {}", path.display(), e);
+    let file = std::fs::File::create(path).map_err(|e| error(&e))?;
+    let json = JsonTrajectory {
+        fps: trajectory.fps,
+        bounds: trajectory.area(),
+        geometry: trajectory.geometry.as_deref(),
+        units: trajectory.units.as_deref(),
+        frames: &trajectory.frames,
+    };
+    serde_json::to_writer(BufWriter::new(file), &json).map_err(|e| error(&e))
+}
+
+pub fn save_png(texture: &Texture2d, path: &Path) -> Result<(), image::ImageError> {
+    let raw: RawImage2d<u8> = texture.read();
+    let image = image::ImageBuffer::<image::Rgba<u8>, _>::from_raw(
+        raw.width,
+        raw.height,
+        raw.data.into_owned(),
+    )
+    .unwrap();
+    // OpenGL stores the bottom row first.
+    image::imageops::flip_vertical(&image).save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_formatted_as_csv() {
+        let frame = Frame {
+            ids: vec![3, 7],
+            positions: vec![[1.5, -2.0], [0.25, 4.0]],
+            time: None,
+            groups: Vec::new(),
+        };
+        assert_eq!(frame_csv(&frame), "id,x,y\n3,1.5,-2\n7,0.25,4\n");
+        assert_eq!(frame_csv(&Frame::new()), "id,x,y\n");
+    }
+
+    #[test]
+    fn json_export_round_trips() {
+        let frames = vec![
+            Frame {
+                ids: vec![1, 2],
+                positions: vec![[0.5, -1.0], [2.0, 3.25]],
+                time: Some(0.0),
+                groups: Vec::new(),
+            },
+            Frame {
+                ids: vec![2],
+                positions: vec![[2.5, 3.0]],
+                time: Some(0.4),
+                groups: Vec::new(),
+            },
+        ];
+        let mut trajectory = Trajectory::new(frames, Some(2.5));
+        trajectory.geometry = Some(PathBuf::from("corridor.wkt"));
+        let file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+        save_trajectory_json(&trajectory, file.path()).unwrap();
+        let imported = crate::parsers::parse_trajectory(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(imported, trajectory);
+    }
+}
@@ -0,0 +1,380 @@
+use regex::Regex;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::error::ParseError;
+
+// `polylines` are walls, i.e. line strings and the outer rings of polygons.
+// `holes` are the inner rings of polygons, which are obstacles inside the
+// walkable area. `doors` are the transitions and crossings of jpscore
+// geometry, agents walk through them.
+#[derive(Debug)]
+pub struct Geometry {
+    pub polylines: Vec<Vec<[f32; 2]>>,
+    pub holes: Vec<Vec<[f32; 2]>>,
+    pub polygons: Vec<Polygon>,
+    pub doors: Vec<Vec<[f32; 2]>>,
+}
+
+// A polygon of the WKT file as indices into `Geometry::polylines` for its
+// outer ring and `Geometry::holes` for its inner rings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub outer: usize,
+    pub holes: Range<usize>,
+}
+
+impl Geometry {
+    pub fn area(&self) -> (f32, f32, f32, f32) {
+        let mut x_min = f32::MAX;
+        let mut y_min = f32::MAX;
+        let mut x_max = f32::MIN;
+        let mut y_max = f32::MIN;
+
+        for polyline in &self.polylines {
+            for p in polyline {
+                x_min = f32::min(p[0], x_min);
+                x_max = f32::max(p[0], x_max);
+                y_min = f32::min(p[1], y_min);
+                y_max = f32::max(p[1], y_max);
+            }
+        }
+        (x_min, x_max, y_min, y_max)
+    }
+
+    // Each wall split into individual segments, suitable for drawing as a
+    // line list.
+    pub fn segments(&self) -> Vec<[f32; 2]> {
+        line_list(&self.polylines)
+    }
+
+    pub fn hole_segments(&self) -> Vec<[f32; 2]> {
+        line_list(&self.holes)
+    }
+
+    pub fn door_segments(&self) -> Vec<[f32; 2]> {
+        line_list(&self.doors)
+    }
+
+    // The walkable area, i.e. all polygons without their holes, as a
+    // triangle list.
+    pub fn triangles(&self) -> Vec<[f32; 2]> {
+        let mut triangles = Vec::new();
+        for polygon in &self.polygons {
+            let mut points: Vec<[f32; 2]> = Vec::new();
+            let mut hole_indices = Vec::new();
+            let rings = std::iter::once(&self.polylines[polygon.outer])
+                .chain(&self.holes[polygon.holes.clone()]);
+            for (index, ring) in rings.enumerate() {
+                if index > 0 {
+                    hole_indices.push(points.len());
+                }
+                points.extend_from_slice(open_ring(ring));
+            }
+            let data: Vec<f64> = points
+                .iter()
+                .flat_map(|p| p.iter().map(|c| *c as f64))
+                .collect();
+            for index in earcutr::earcut(&data, &hole_indices, 2) {
+                triangles.push(points[index]);
+            }
+        }
+        triangles
+    }
+}
+
+// `ring` without the repeated first point closing it.
+fn open_ring(ring: &[[f32; 2]]) -> &[[f32; 2]] {
+    match ring.split_last() {
+        Some((last, rest)) if rest.first() == Some(last) => rest,
+        _ => ring,
+    }
+}
+
+fn line_list(polylines: &[Vec<[f32; 2]>]) -> Vec<[f32; 2]> {
+    let mut segments = Vec::new();
+    for polyline in polylines {
+        for pair in polyline.windows(2) {
+            segments.push(pair[0]);
+            segments.push(pair[1]);
+        }
+    }
+    segments
+}
+
+// Reads every coordinate list of a WKT file, e.g. the rings of `POLYGON` and
+// `MULTIPOLYGON` or the points of `LINESTRING`, as a polyline. Polygon rings
+// repeat their first point at the end and therefore come out closed. Every
+// ring of a polygon but the first one is a hole.
+pub fn parse_geometry_wkt(path: &Path) -> Result<Geometry, ParseError> {
+    let content = std::fs::read_to_string(path)?;
+    parse_wkt(&content)
+}
+
+pub fn parse_wkt(content: &str) -> Result<Geometry, ParseError> {
+    let list_matcher = Regex::new(r"\(([^()]*)\)").unwrap();
+    // Geometry types like `POLYGON`, the single letters of exponents are no
+    // tags.
+    let tags: Vec<(usize, bool)> = Regex::new(r"[A-Za-z]{2,}")
+        .unwrap()
+        .find_iter(content)
+        .map(|m| {
+            (
+                m.start(),
+                m.as_str().to_ascii_uppercase().ends_with("POLYGON"),
+            )
+        })
+        .collect();
+    let mut polylines = Vec::new();
+    let mut holes = Vec::new();
+    let mut polygons: Vec<Polygon> = Vec::new();
+    for captures in list_matcher.captures_iter(content) {
+        let list = captures.get(1).unwrap();
+        let start = captures.get(0).unwrap().start();
+        let tag = tags.partition_point(|(position, _)| *position < start);
+        let in_polygon = tag > 0 && tags[tag - 1].1;
+        let is_hole = in_polygon && content[..start].trim_end().ends_with(',');
+        let syntax_error = |message: String| ParseError::Syntax {
+            line: content[..list.start()].matches('\n').count() + 1,
+            message,
+        };
+        let mut polyline = Vec::new();
+        for point in list.as_str().split(',') {
+            let coordinates = point
+                .split_whitespace()
+                .map(|c| c.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|e| {
+                    syntax_error(format!("invalid coordinate in '{}': {}", point.trim(), e))
+                })?;
+            match coordinates.as_slice() {
+                [x, y, ..] => polyline.push([*x, *y]),
+                _ => return Err(syntax_error(format!("invalid point '{}'", point.trim()))),
+            }
+        }
+        if polyline.len() > 1 {
+            if is_hole {
+                holes.push(polyline);
+                if let Some(polygon) = polygons.last_mut() {
+                    polygon.holes.end = holes.len();
+                }
+            } else {
+                if in_polygon {
+                    polygons.push(Polygon {
+                        outer: polylines.len(),
+                        holes: holes.len()..holes.len(),
+                    });
+                }
+                polylines.push(polyline);
+            }
+        }
+    }
+    if polylines.is_empty() {
+        return Err(ParseError::Invalid("no polylines found".to_string()));
+    }
+    Ok(Geometry {
+        polylines,
+        holes,
+        polygons,
+        doors: Vec::new(),
+    })
+}
+
+pub fn parse_geometry_xml(path: &Path) -> Result<Geometry, ParseError> {
+    let content = std::fs::read_to_string(path)?;
+    parse_xml(&content)
+}
+
+// Reads the `geometry.xml` files of jpscore. Every `polygon` of a subroom
+// is a wall, polygons of an `obstacle` are closed and become holes, and
+// `transition` and `crossing` elements become doors. Rooms and subrooms
+// only group these and are not kept.
+pub fn parse_xml(content: &str) -> Result<Geometry, ParseError> {
+    // Commented out elements are blanked, keeping line numbers intact.
+    let content = Regex::new(r"(?s)<!--.*?-->")
+        .unwrap()
+        .replace_all(content, |captures: &regex::Captures| {
+            captures[0].replace(|c: char| c != '\n', " ")
+        });
+    let tag_matcher =
+        Regex::new(r"<(/?)(polygon|obstacle|transition|crossing|vertex)\b([^>]*)>").unwrap();
+    let px_matcher = Regex::new(r#"\bpx\s*=\s*["']([^"']*)["']"#).unwrap();
+    let py_matcher = Regex::new(r#"\bpy\s*=\s*["']([^"']*)["']"#).unwrap();
+    let mut polylines = Vec::new();
+    let mut holes = Vec::new();
+    let mut doors = Vec::new();
+    let mut in_obstacle = false;
+    let mut points: Vec<[f32; 2]> = Vec::new();
+    let (mut line, mut counted_to) = (1, 0);
+    for captures in tag_matcher.captures_iter(&content) {
+        let start = captures.get(0).unwrap().start();
+        line += content[counted_to..start].matches('\n').count();
+        counted_to = start;
+        let closing = !captures[1].is_empty();
+        let attributes = &captures[3];
+        match (&captures[2], closing) {
+            ("vertex", _) => {
+                let coordinate = |matcher: &Regex, name: &str| {
+                    matcher
+                        .captures(attributes)
+                        .and_then(|value| value[1].trim().parse::<f32>().ok())
+                        .ok_or_else(|| ParseError::Syntax {
+                            line,
+                            message: format!("vertex without a valid '{}' attribute", name),
+                        })
+                };
+                points.push([
+                    coordinate(&px_matcher, "px")?,
+                    coordinate(&py_matcher, "py")?,
+                ]);
+            }
+            ("obstacle", closing) => in_obstacle = !closing,
+            (_, false) => points.clear(),
+            ("polygon", true) => {
+                let mut polyline = std::mem::take(&mut points);
+                if polyline.len() < 2 {
+                    continue;
+                }
+                if in_obstacle {
+                    if polyline.first() != polyline.last() {
+                        polyline.push(polyline[0]);
+                    }
+                    holes.push(polyline);
+                } else {
+                    polylines.push(polyline);
+                }
+            }
+            (_, true) => {
+                let door = std::mem::take(&mut points);
+                if door.len() > 1 {
+                    doors.push(door);
+                }
+            }
+        }
+    }
+    if polylines.is_empty() {
+        return Err(ParseError::Invalid("no walls found".to_string()));
+    }
+    Ok(Geometry {
+        polylines,
+        holes,
+        polygons: Vec::new(),
+        doors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_polygon_with_hole() {
+        let geometry =
+            parse_wkt("POLYGON ((0 0, 10 0, 10 5, 0 5, 0 0), (2 2, 3 2, 3 3, 2 2))").unwrap();
+        assert_eq!(geometry.polylines.len(), 1);
+        assert_eq!(geometry.holes.len(), 1);
+        assert_eq!(geometry.polylines[0].len(), 5);
+        assert_eq!(geometry.area(), (0.0, 10.0, 0.0, 5.0));
+        assert_eq!(geometry.segments().len(), 2 * 4);
+        assert_eq!(geometry.hole_segments().len(), 2 * 3);
+    }
+
+    #[test]
+    fn triangles_cover_walkable_area() {
+        let geometry =
+            parse_wkt("POLYGON ((0 0, 10 0, 10 5, 0 5, 0 0), (2 2, 4 2, 4 4, 2 4, 2 2))").unwrap();
+        let triangles = geometry.triangles();
+        assert_eq!(triangles.len() % 3, 0);
+        let area: f32 = triangles
+            .chunks(3)
+            .map(|t| {
+                ((t[1][0] - t[0][0]) * (t[2][1] - t[0][1])
+                    - (t[2][0] - t[0][0]) * (t[1][1] - t[0][1]))
+                    .abs()
+                    / 2.0
+            })
+            .sum();
+        assert!((area - 46.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn parses_multipolygon_fixture() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("corridor.wkt");
+        let geometry = parse_geometry_wkt(&path).unwrap();
+        assert_eq!(geometry.polylines.len(), 2);
+        assert_eq!(geometry.holes.len(), 2);
+        assert_eq!(geometry.area(), (0.0, 20.0, 0.0, 4.0));
+        assert_eq!(geometry.holes[1][0], [14.0, 1.5]);
+        assert_eq!(
+            geometry.polygons[1],
+            Polygon {
+                outer: 1,
+                holes: 1..2
+            }
+        );
+    }
+
+    #[test]
+    fn line_strings_have_no_holes() {
+        let geometry = parse_wkt("MULTILINESTRING ((0 0, 1 0), (0 1, 1 1))").unwrap();
+        assert_eq!(geometry.polylines.len(), 2);
+        assert!(geometry.holes.is_empty());
+        assert!(geometry.triangles().is_empty());
+    }
+
+    #[test]
+    fn parses_jpscore_xml() {
+        let geometry = parse_xml(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <geometry version="0.8" unit="m">
+              <rooms>
+                <room id="0">
+                  <subroom id="0" class="subroom">
+                    <polygon caption="wall">
+                      <vertex px="0" py="0" />
+                      <vertex px="10" py="0" />
+                      <vertex px="10" py="2" />
+                    </polygon>
+                    <!-- <polygon><vertex px="5" py="5"/><vertex px="6" py="6"/></polygon> -->
+                    <obstacle id="0">
+                      <polygon>
+                        <vertex px="4" py="1" /><vertex px="5" py="1" /><vertex px="5" py="2" />
+                      </polygon>
+                    </obstacle>
+                  </subroom>
+                </room>
+              </rooms>
+              <transitions>
+                <transition id="0" caption="exit" room1_id="0" room2_id="-1">
+                  <vertex px="10" py="2" />
+                  <vertex px="10" py="4" />
+                </transition>
+              </transitions>
+            </geometry>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            geometry.polylines,
+            vec![vec![[0.0, 0.0], [10.0, 0.0], [10.0, 2.0]]]
+        );
+        assert_eq!(geometry.holes.len(), 1);
+        assert_eq!(geometry.holes[0].first(), geometry.holes[0].last());
+        assert_eq!(geometry.doors, vec![vec![[10.0, 2.0], [10.0, 4.0]]]);
+        assert!(geometry.triangles().is_empty());
+    }
+
+    #[test]
+    fn rejects_xml_without_walls() {
+        assert!(parse_xml("<geometry><rooms></rooms></geometry>").is_err());
+        assert!(parse_xml(r#"<polygon><vertex px="1" /></polygon>"#).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_points() {
+        assert!(parse_wkt("LINESTRING (0 0, 1 x)").is_err());
+        assert!(parse_wkt("").is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use glium::glutin::dpi::PhysicalSize;
+use glium::glutin::event_loop::EventLoop;
+use glium::glutin::ContextBuilder;
+use glium::texture::Texture2d;
+use glium::HeadlessRenderer;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::cli::Args;
+use crate::export;
+use crate::heatmap::Heatmap;
+use crate::parsers;
+use crate::renderer::Renderer2D;
+use crate::ApplicationState;
+
+// Summary printed by `--bench`, times are in milliseconds.
+#[derive(Debug, Serialize)]
+struct BenchReport {
+    version: &'static str,
+    trajectory: PathBuf,
+    frames: usize,
+    max_agents: usize,
+    width: u32,
+    height: u32,
+    parse_ms: f64,
+    render_total_ms: f64,
+    render_mean_ms: f64,
+    render_max_ms: f64,
+    // Highest resident memory of the process, only known on Linux.
+    peak_memory_bytes: Option<u64>,
+}
+
+// Everything needed to render frames of the trajectory given on the
+// command line, without a window.
+struct Headless {
+    state: ApplicationState,
+    renderer: Renderer2D,
+    facade: HeadlessRenderer,
+    heatmap: Heatmap,
+    bounds: (f32, f32, f32, f32),
+    size: (u32, u32),
+    parse_time: Duration,
+}
+
+impl Headless {
+    // Loads the trajectory with the saved settings and fits the view to it.
+    fn new(args: &Args, event_loop: &EventLoop<()>) -> Result<Self, String> {
+        let path = args
+            .trajectory
+            .as_ref()
+            .ok_or("Rendering frames needs a trajectory")?;
+        if args.width == 0 || args.height == 0 {
+            return Err(format!("Invalid image size {}x{}", args.width, args.height));
+        }
+        let mut state = ApplicationState::new();
+        state.load_settings();
+        let start = Instant::now();
+        let trajectory = parsers::parse_trajectory(path, &mut |_| {})
+            .map_err(|e| format!("Cannot load '{}': {}", path.display(), e))?;
+        let parse_time = start.elapsed();
+        state.show_trajectory(path, trajectory);
+        // Replaces the geometry named in the trajectory header, if any.
+        if let Some(geometry) = args.geometry.as_ref() {
+            state
+                .open_geometry(geometry)
+                .map_err(|e| format!("Cannot load '{}': {}", geometry.display(), e))?;
+        }
+        for line in state.console.lines() {
+            eprintln!("{}", line);
+        }
+
+        let context = ContextBuilder::new()
+            .build_headless(event_loop, PhysicalSize::new(args.width, args.height))
+            .map_err(|e| format!("Cannot create an OpenGL context: {}", e))?;
+        let facade = HeadlessRenderer::new(context)
+            .map_err(|e| format!("Cannot create an OpenGL context: {}", e))?;
+        let mut renderer = Renderer2D::new(&facade).map_err(|e| e.to_string())?;
+        renderer.set_geometry(state.geometry.as_ref());
+
+        let smoothing = state.settings.smoothing_window as usize;
+        for layer in state.layers.iter_mut() {
+            layer.replay.set_smoothing(smoothing);
+        }
+        state.camera.set_viewport(args.width, args.height);
+        state.reset_view();
+        let bounds = state.camera.bounds();
+        Ok(Self {
+            state,
+            renderer,
+            facade,
+            heatmap: Heatmap::new(),
+            bounds,
+            size: (args.width, args.height),
+            parse_time,
+        })
+    }
+
+    fn frames(&self) -> usize {
+        self.state.replay().map_or(0, |replay| replay.frames())
+    }
+
+    fn render(&mut self, frame: usize) -> Result<Texture2d, String> {
+        let frame_index = {
+            let replay = self.state.replay_mut().ok_or("No trajectory loaded")?;
+            if frame >= replay.frames() {
+                return Err(format!(
+                    "Frame {} is out of range, the trajectory has {} frames",
+                    frame,
+                    replay.frames()
+                ));
+            }
+            replay.seek(frame);
+            replay.current_frame_index
+        };
+        let settings = &self.state.settings;
+        let heatmap = match self.state.replay() {
+            Some(replay) if settings.show_heatmap => {
+                self.heatmap.update(
+                    replay,
+                    frame_index,
+                    settings.heatmap_cell_size,
+                    settings.cumulative_heatmap,
+                );
+                Some(&self.heatmap)
+            }
+            _ => None,
+        };
+        Ok(self.renderer.render_to_texture(
+            &self.state,
+            frame_index,
+            heatmap,
+            self.bounds,
+            self.size,
+        ))
+    }
+}
+
+// Renders the frames requested with `--render-frame` to PNG images without
+// opening a window, using the saved settings.
+pub fn run(args: &Args) -> Result<(), String> {
+    let event_loop = EventLoop::new();
+    let mut headless = Headless::new(args, &event_loop)?;
+    for &frame in &args.render_frames {
+        let texture = headless.render(frame)?;
+        let out = image_path(&args.out, frame, args.render_frames.len() > 1);
+        export::save_png(&texture, &out)
+            .map_err(|e| format!("Cannot write '{}': {}", out.display(), e))?;
+        eprintln!("Rendered frame {} to '{}'", frame, out.display());
+    }
+    Ok(())
+}
+
+// Times loading the trajectory and rendering every frame offscreen, the
+// summary is printed to stdout as json.
+pub fn bench(args: &Args) -> Result<(), String> {
+    let event_loop = EventLoop::new();
+    let mut headless = Headless::new(args, &event_loop)?;
+    let mut frame_times = Vec::with_capacity(headless.frames());
+    for frame in 0..headless.frames() {
+        let start = Instant::now();
+        headless.render(frame)?;
+        // Waits for the GPU, otherwise only queueing the commands is timed.
+        headless.facade.finish();
+        frame_times.push(start.elapsed());
+    }
+    let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    let total: Duration = frame_times.iter().sum();
+    let report = BenchReport {
+        version: env!("CARGO_PKG_VERSION"),
+        trajectory: args.trajectory.clone().unwrap_or_default(),
+        frames: frame_times.len(),
+        max_agents: headless
+            .state
+            .replay()
+            .map_or(0, |replay| replay.max_agent_count()),
+        width: args.width,
+        height: args.height,
+        parse_ms: millis(headless.parse_time),
+        render_total_ms: millis(total),
+        render_mean_ms: millis(total) / frame_times.len().max(1) as f64,
+        render_max_ms: frame_times.iter().copied().max().map_or(0.0, millis),
+        peak_memory_bytes: std::fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|status| peak_memory(&status)),
+    };
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    println!("{}", json);
+    Ok(())
+}
+
+// Parses the `VmHWM` line of `/proc/self/status`, given in kB.
+fn peak_memory(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes: u64 = line["VmHWM:".len()..]
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+// `out` itself for a single image, otherwise the frame number is appended
+// to the file name, e.g. `shot_0120.png`.
+fn image_path(out: &Path, frame: usize, numbered: bool) -> PathBuf {
+    if !numbered {
+        return out.to_path_buf();
+    }
+    let stem = out
+        .file_stem()
+        .map_or_else(|| "frame".into(), |s| s.to_string_lossy());
+    let extension = out
+        .extension()
+        .map_or_else(|| "png".into(), |e| e.to_string_lossy());
+    out.with_file_name(format!("{}_{:04}.{}", stem, frame, extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn several_frames_get_numbered_paths() {
+        let out = Path::new("out/shot.png");
+        assert_eq!(image_path(out, 120, false), PathBuf::from("out/shot.png"));
+        assert_eq!(
+            image_path(out, 120, true),
+            PathBuf::from("out/shot_0120.png")
+        );
+        assert_eq!(
+            image_path(Path::new("shot"), 7, true),
+            PathBuf::from("shot_0007.png")
+        );
+    }
+
+    #[test]
+    fn peak_memory_is_read_from_proc_status() {
+        let status = "Name:\tvistwo\nVmPeak:\t  812340 kB\nVmHWM:\t   20480 kB\n";
+        assert_eq!(peak_memory(status), Some(20480 * 1024));
+        assert_eq!(peak_memory("Name:\tvistwo\n"), None);
+    }
+}
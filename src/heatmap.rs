@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use crate::legacy_parsers::Frame;
+use crate::replay::Replay;
+
+// Number of agents per square cell of `cell_size` meters, cells are aligned
+// to the world origin. In cumulative mode the counts of all frames up to the
+// current one are summed, otherwise only the current frame is binned.
+#[derive(Debug)]
+pub struct Heatmap {
+    cell_size: f32,
+    cumulative: bool,
+    cells: HashMap<(i32, i32), u32>,
+    // Last frame that went into `cells`, lets a cumulative heatmap be
+    // extended incrementally while the replay moves forward.
+    binned_until: Option<usize>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Self {
+            cell_size: 1.0,
+            cumulative: false,
+            cells: HashMap::new(),
+            binned_until: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.cells.clear();
+        self.binned_until = None;
+    }
+
+    pub fn update(
+        &mut self,
+        replay: &Replay,
+        frame_index: usize,
+        cell_size: f32,
+        cumulative: bool,
+    ) {
+        if cell_size <= 0.0 {
+            self.reset();
+            return;
+        }
+        if cell_size != self.cell_size || cumulative != self.cumulative {
+            self.cell_size = cell_size;
+            self.cumulative = cumulative;
+            self.reset();
+        }
+        match self.binned_until {
+            Some(index) if index == frame_index => return,
+            Some(index) if cumulative && index < frame_index => {
+                for index in index + 1..=frame_index {
+                    self.add(replay.frame(index));
+                }
+            }
+            _ if cumulative => {
+                self.reset();
+                for index in 0..=frame_index {
+                    self.add(replay.frame(index));
+                }
+            }
+            _ => {
+                self.reset();
+                self.add(replay.frame(frame_index));
+            }
+        }
+        self.binned_until = Some(frame_index);
+    }
+
+    fn add(&mut self, frame: &Frame) {
+        for p in &frame.positions {
+            let cell = (
+                (p[0] / self.cell_size).floor() as i32,
+                (p[1] / self.cell_size).floor() as i32,
+            );
+            *self.cells.entry(cell).or_insert(0) += 1;
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn max_count(&self) -> u32 {
+        self.cells.values().copied().max().unwrap_or(0)
+    }
+
+    // Center of every non empty cell together with its count.
+    pub fn cells(&self) -> impl Iterator<Item = ([f32; 2], u32)> + '_ {
+        self.cells.iter().map(move |(&(x, y), &count)| {
+            let center = [
+                (x as f32 + 0.5) * self.cell_size,
+                (y as f32 + 0.5) * self.cell_size,
+            ];
+            (center, count)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::legacy_parsers::Trajectory;
+    use std::time::Duration;
+
+    fn replay() -> Replay {
+        let trajectory = Trajectory::new(
+            vec![
+                Frame {
+                    ids: vec![1, 2],
+                    positions: vec![[0.2, 0.2], [0.7, 0.4]],
+                    time: None,
+                    groups: Vec::new(),
+                },
+                Frame {
+                    ids: vec![1, 2],
+                    positions: vec![[0.4, 0.2], [1.5, -0.5]],
+                    time: None,
+                    groups: Vec::new(),
+                },
+            ],
+            None,
+        );
+        Replay::new(trajectory, Duration::from_millis(100))
+    }
+
+    #[test]
+    fn instant_heatmap_bins_current_frame() {
+        let replay = replay();
+        let mut heatmap = Heatmap::new();
+        heatmap.update(&replay, 0, 1.0, false);
+        assert_eq!(heatmap.cells().collect::<Vec<_>>(), vec![([0.5, 0.5], 2)]);
+        heatmap.update(&replay, 1, 1.0, false);
+        let mut cells: Vec<_> = heatmap.cells().collect();
+        cells.sort_by(|a, b| a.0[0].partial_cmp(&b.0[0]).unwrap());
+        assert_eq!(cells, vec![([0.5, 0.5], 1), ([1.5, -0.5], 1)]);
+    }
+
+    #[test]
+    fn cumulative_heatmap_sums_frames() {
+        let replay = replay();
+        let mut heatmap = Heatmap::new();
+        heatmap.update(&replay, 1, 1.0, true);
+        assert_eq!(heatmap.max_count(), 3);
+        heatmap.update(&replay, 0, 1.0, true);
+        assert_eq!(heatmap.max_count(), 2);
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use crate::legacy_parsers::Frame;
+
+// Side length of the cells agents are counted in when scoring frames.
+pub const CELL_SIZE: f32 = 1.0;
+
+// Highest number of agents found in a single `cell_size` cell of the frame.
+pub fn peak_density(frame: &Frame, cell_size: f32) -> f32 {
+    let mut cells = HashMap::new();
+    for p in &frame.positions {
+        let cell = (
+            (p[0] / cell_size).floor() as i32,
+            (p[1] / cell_size).floor() as i32,
+        );
+        *cells.entry(cell).or_insert(0u32) += 1;
+    }
+    cells.values().copied().max().unwrap_or(0) as f32
+}
+
+// A frame is a hotspot if its score is a local maximum of at least
+// `min_score`. For plateaus only the first frame counts.
+fn is_hotspot(scores: &[f32], index: usize, min_score: f32) -> bool {
+    let score = scores[index];
+    if score <= 0.0 || score < min_score {
+        return false;
+    }
+    let rises = index == 0 || scores[index - 1] < score;
+    let falls = scores[index + 1..]
+        .iter()
+        .find(|s| **s != score)
+        .is_none_or(|s| *s < score);
+    rises && falls
+}
+
+// `threshold` is relative to the highest score of all frames.
+fn min_score(scores: &[f32], threshold: f32) -> f32 {
+    threshold * scores.iter().copied().fold(0.0, f32::max)
+}
+
+pub fn next_hotspot(scores: &[f32], from: usize, threshold: f32) -> Option<usize> {
+    let min_score = min_score(scores, threshold);
+    (from + 1..scores.len()).find(|i| is_hotspot(scores, *i, min_score))
+}
+
+pub fn prev_hotspot(scores: &[f32], from: usize, threshold: f32) -> Option<usize> {
+    let min_score = min_score(scores, threshold);
+    (0..from.min(scores.len()))
+        .rev()
+        .find(|i| is_hotspot(scores, *i, min_score))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peak_density_counts_busiest_cell() {
+        let frame = Frame {
+            ids: vec![1, 2, 3, 4],
+            positions: vec![[0.1, 0.1], [0.9, 0.5], [0.5, 0.2], [3.0, 3.0]],
+            time: None,
+            groups: Vec::new(),
+        };
+        assert_eq!(peak_density(&frame, 1.0), 3.0);
+        assert_eq!(peak_density(&Frame::new(), 1.0), 0.0);
+    }
+
+    #[test]
+    fn hotspots_are_local_maxima_above_threshold() {
+        let scores = [1.0, 4.0, 2.0, 2.0, 10.0, 10.0, 3.0, 6.0, 1.0];
+        assert_eq!(next_hotspot(&scores, 0, 0.3), Some(1));
+        assert_eq!(next_hotspot(&scores, 1, 0.3), Some(4));
+        assert_eq!(next_hotspot(&scores, 4, 0.3), Some(7));
+        assert_eq!(next_hotspot(&scores, 7, 0.3), None);
+        assert_eq!(next_hotspot(&scores, 0, 0.7), Some(4));
+        assert_eq!(prev_hotspot(&scores, 7, 0.3), Some(4));
+        assert_eq!(prev_hotspot(&scores, 4, 0.3), Some(1));
+        assert_eq!(prev_hotspot(&scores, 1, 0.3), None);
+    }
+}
@@ -0,0 +1,303 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use winit::event::VirtualKeyCode;
+
+use crate::keymap::KeyMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    TogglePlay,
+    StepForward,
+    StepBack,
+    JumpForward,
+    JumpBack,
+    JumpToStart,
+    JumpToEnd,
+    ResetView,
+    ToggleMeasure,
+    ToggleFrameTime,
+    FollowAgent,
+    Reload,
+    Exit,
+}
+
+impl Action {
+    pub const ALL: [Action; 13] = [
+        Action::TogglePlay,
+        Action::StepForward,
+        Action::StepBack,
+        Action::JumpForward,
+        Action::JumpBack,
+        Action::JumpToStart,
+        Action::JumpToEnd,
+        Action::ResetView,
+        Action::ToggleMeasure,
+        Action::ToggleFrameTime,
+        Action::FollowAgent,
+        Action::Reload,
+        Action::Exit,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::TogglePlay => "Play / pause",
+            Action::StepForward => "Step forward",
+            Action::StepBack => "Step back",
+            Action::JumpForward => "Jump forward",
+            Action::JumpBack => "Jump back",
+            Action::JumpToStart => "Jump to start",
+            Action::JumpToEnd => "Jump to end",
+            Action::ResetView => "Reset view",
+            Action::ToggleMeasure => "Measure distances",
+            Action::ToggleFrameTime => "Toggle HUD",
+            Action::FollowAgent => "Follow selected agent",
+            Action::Reload => "Reload trajectory",
+            Action::Exit => "Exit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyCombo {
+    pub key: VirtualKeyCode,
+    #[serde(default)]
+    pub shift: bool,
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub alt: bool,
+}
+
+impl KeyCombo {
+    pub fn new(key: VirtualKeyCode) -> Self {
+        Self {
+            key,
+            shift: false,
+            ctrl: false,
+            alt: false,
+        }
+    }
+
+    pub fn with_shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    // `key` together with the modifiers currently held down.
+    pub fn held(key: VirtualKeyCode, keymap: &KeyMap) -> Self {
+        Self {
+            key,
+            shift: keymap.shift(),
+            ctrl: keymap.ctrl(),
+            alt: keymap.alt(),
+        }
+    }
+
+    pub fn is_modifier(key: VirtualKeyCode) -> bool {
+        matches!(
+            key,
+            VirtualKeyCode::LShift
+                | VirtualKeyCode::RShift
+                | VirtualKeyCode::LControl
+                | VirtualKeyCode::RControl
+                | VirtualKeyCode::LAlt
+                | VirtualKeyCode::RAlt
+        )
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub action: Action,
+    #[serde(flatten)]
+    pub combo: KeyCombo,
+}
+
+// Maps key combinations to actions, each action has at most one binding.
+// Modifiers have to match exactly, so `Right` and `Shift+Right` can trigger
+// different actions.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Keybindings {
+    bindings: Vec<Binding>,
+}
+
+// Actions missing from a saved table, e.g. ones added in a later version,
+// keep their default binding.
+impl<'de> Deserialize<'de> for Keybindings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut keybindings = Self {
+            bindings: Vec::deserialize(deserializer)?,
+        };
+        for binding in Self::new().bindings {
+            if keybindings.combo(binding.action).is_none() {
+                keybindings.bindings.push(binding);
+            }
+        }
+        Ok(keybindings)
+    }
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keybindings {
+    pub fn new() -> Self {
+        let binding = |action, combo| Binding { action, combo };
+        Self {
+            bindings: vec![
+                binding(Action::TogglePlay, KeyCombo::new(VirtualKeyCode::Space)),
+                binding(Action::StepForward, KeyCombo::new(VirtualKeyCode::Right)),
+                binding(Action::StepBack, KeyCombo::new(VirtualKeyCode::Left)),
+                binding(
+                    Action::JumpForward,
+                    KeyCombo::new(VirtualKeyCode::Right).with_shift(),
+                ),
+                binding(
+                    Action::JumpBack,
+                    KeyCombo::new(VirtualKeyCode::Left).with_shift(),
+                ),
+                binding(Action::JumpToStart, KeyCombo::new(VirtualKeyCode::Home)),
+                binding(Action::JumpToEnd, KeyCombo::new(VirtualKeyCode::End)),
+                binding(Action::ResetView, KeyCombo::new(VirtualKeyCode::R)),
+                binding(Action::ToggleMeasure, KeyCombo::new(VirtualKeyCode::M)),
+                binding(Action::ToggleFrameTime, KeyCombo::new(VirtualKeyCode::F3)),
+                binding(Action::FollowAgent, KeyCombo::new(VirtualKeyCode::F)),
+                binding(Action::Reload, KeyCombo::new(VirtualKeyCode::F5)),
+                binding(Action::Exit, KeyCombo::new(VirtualKeyCode::Escape)),
+            ],
+        }
+    }
+
+    pub fn combo(&self, action: Action) -> Option<KeyCombo> {
+        self.bindings
+            .iter()
+            .find(|b| b.action == action)
+            .map(|b| b.combo)
+    }
+
+    pub fn bind(&mut self, action: Action, combo: KeyCombo) {
+        self.bindings.retain(|b| b.action != action);
+        self.bindings.push(Binding { action, combo });
+    }
+
+    // Actions other than `action` bound to the same combination.
+    pub fn conflicts(&self, action: Action) -> Vec<Action> {
+        let combo = match self.combo(action) {
+            Some(combo) => combo,
+            None => return Vec::new(),
+        };
+        self.bindings
+            .iter()
+            .filter(|b| b.action != action && b.combo == combo)
+            .map(|b| b.action)
+            .collect()
+    }
+
+    // Whether the combination bound to `action` is held down right now.
+    pub fn is_held(&self, action: Action, keymap: &KeyMap) -> bool {
+        self.combo(action).is_some_and(|combo| {
+            keymap.is_pressed(combo.key) && KeyCombo::held(combo.key, keymap) == combo
+        })
+    }
+
+    // Actions whose key was pressed this frame with exactly the bound
+    // modifiers held.
+    pub fn triggered(&self, keymap: &KeyMap) -> Vec<Action> {
+        self.bindings
+            .iter()
+            .filter(|b| {
+                keymap.just_pressed(b.combo.key) && KeyCombo::held(b.combo.key, keymap) == b.combo
+            })
+            .map(|b| b.action)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let keybindings = Keybindings::new();
+        for action in Action::ALL.iter() {
+            assert!(keybindings.combo(*action).is_some(), "{:?}", action);
+            assert!(keybindings.conflicts(*action).is_empty(), "{:?}", action);
+        }
+    }
+
+    #[test]
+    fn rebinding_replaces_and_reports_conflicts() {
+        let mut keybindings = Keybindings::new();
+        keybindings.bind(Action::Exit, KeyCombo::new(VirtualKeyCode::Space));
+        assert_eq!(
+            keybindings.combo(Action::Exit),
+            Some(KeyCombo::new(VirtualKeyCode::Space))
+        );
+        assert_eq!(
+            keybindings.conflicts(Action::Exit),
+            vec![Action::TogglePlay]
+        );
+        assert_eq!(
+            keybindings.conflicts(Action::TogglePlay),
+            vec![Action::Exit]
+        );
+    }
+
+    #[test]
+    fn keybindings_round_trip_through_toml() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Wrapper {
+            keybindings: Keybindings,
+        }
+        let wrapper = Wrapper {
+            keybindings: Keybindings::new(),
+        };
+        let content = toml::to_string(&wrapper).unwrap();
+        assert_eq!(toml::from_str::<Wrapper>(&content).unwrap(), wrapper);
+    }
+
+    #[test]
+    fn missing_actions_keep_their_default_binding() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            keybindings: Keybindings,
+        }
+        let content = r#"
+            [[keybindings]]
+            action = "Exit"
+            key = "Q"
+        "#;
+        let keybindings = toml::from_str::<Wrapper>(content).unwrap().keybindings;
+        assert_eq!(
+            keybindings.combo(Action::Exit),
+            Some(KeyCombo::new(VirtualKeyCode::Q))
+        );
+        assert_eq!(
+            keybindings.combo(Action::Reload),
+            Some(KeyCombo::new(VirtualKeyCode::F5))
+        );
+        for action in Action::ALL.iter() {
+            assert!(keybindings.combo(*action).is_some(), "{:?}", action);
+        }
+    }
+}
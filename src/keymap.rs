@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fmt::Debug;
 use winit::event::ElementState;
 use winit::event::Event;
@@ -5,9 +6,14 @@ use winit::event::KeyboardInput;
 use winit::event::VirtualKeyCode;
 use winit::event::WindowEvent;
 
+// Tracks which keys are currently held down. A key stays pressed from its
+// `Pressed` event until the matching `Released` event, so held keys persist
+// across frames. `begin_frame` only takes a snapshot of the held keys which
+// `just_pressed` and `just_released` compare against.
 #[derive(Debug)]
 pub struct KeyMap {
-    pressed_keys: Vec<VirtualKeyCode>,
+    held_keys: HashSet<VirtualKeyCode>,
+    previous_keys: HashSet<VirtualKeyCode>,
 }
 
 impl Default for KeyMap {
@@ -19,12 +25,41 @@ impl Default for KeyMap {
 impl KeyMap {
     pub fn new() -> Self {
         Self {
-            pressed_keys: Vec::new(),
+            held_keys: HashSet::new(),
+            previous_keys: HashSet::new(),
         }
     }
 
     pub fn begin_frame(&mut self) {
-        self.pressed_keys.clear();
+        self.previous_keys.clone_from(&self.held_keys);
+    }
+
+    pub fn is_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.held_keys.contains(&key)
+    }
+
+    pub fn just_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.held_keys.contains(&key) && !self.previous_keys.contains(&key)
+    }
+
+    pub fn just_released(&self, key: VirtualKeyCode) -> bool {
+        !self.held_keys.contains(&key) && self.previous_keys.contains(&key)
+    }
+
+    pub fn just_pressed_keys(&self) -> impl Iterator<Item = VirtualKeyCode> + '_ {
+        self.held_keys.difference(&self.previous_keys).copied()
+    }
+
+    pub fn shift(&self) -> bool {
+        self.is_pressed(VirtualKeyCode::LShift) || self.is_pressed(VirtualKeyCode::RShift)
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.is_pressed(VirtualKeyCode::LControl) || self.is_pressed(VirtualKeyCode::RControl)
+    }
+
+    pub fn alt(&self) -> bool {
+        self.is_pressed(VirtualKeyCode::LAlt) || self.is_pressed(VirtualKeyCode::RAlt)
     }
 
     pub fn handle_event<T>(&mut self, evt: &Event<T>)
@@ -37,7 +72,7 @@ impl KeyMap {
                     input:
                         KeyboardInput {
                             virtual_keycode: Some(key),
-                            state: ElementState::Pressed,
+                            state,
                             ..
                         },
                     ..
@@ -45,7 +80,93 @@ impl KeyMap {
             ..
         } = evt
         {
-            self.pressed_keys.push(*key);
+            match state {
+                ElementState::Pressed => self.held_keys.insert(*key),
+                ElementState::Released => self.held_keys.remove(key),
+            };
         };
     }
 }
+
+// Repeats per second right after the initial delay, the rate grows to the
+// maximum over `REPEAT_RAMP` seconds.
+const REPEAT_START_RATE: f32 = 5.0;
+const REPEAT_RAMP: f32 = 1.5;
+
+// Turns a held key into repeated steps. The key press itself is handled as
+// usual, repeats start once the key is held longer than the initial delay
+// and then accelerate from `REPEAT_START_RATE` up to the maximum rate.
+#[derive(Debug)]
+pub struct KeyRepeat {
+    held_for: f32,
+    // Fractional repeats carried over to the next frame. Starts at one so the
+    // first repeat fires as soon as the delay has passed.
+    pending: f32,
+}
+
+impl Default for KeyRepeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyRepeat {
+    pub fn new() -> Self {
+        Self {
+            held_for: 0.0,
+            pending: 1.0,
+        }
+    }
+
+    // Number of repeats during the last `delta_time` seconds, releasing the
+    // key starts over.
+    pub fn update(&mut self, held: bool, delta_time: f32, delay: f32, max_rate: f32) -> u32 {
+        if !held {
+            *self = Self::new();
+            return 0;
+        }
+        self.held_for += delta_time;
+        let repeating = self.held_for - delay;
+        if repeating <= 0.0 {
+            return 0;
+        }
+        let ramp = (repeating / REPEAT_RAMP).min(1.0);
+        let rate = REPEAT_START_RATE + (max_rate - REPEAT_START_RATE).max(0.0) * ramp;
+        self.pending += rate * delta_time.min(repeating);
+        let repeats = self.pending.floor();
+        self.pending -= repeats;
+        repeats as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hold(repeat: &mut KeyRepeat, seconds: f32) -> u32 {
+        let frames = (seconds * 100.0).round() as usize;
+        (0..frames)
+            .map(|_| repeat.update(true, 0.01, 0.3, 30.0))
+            .sum()
+    }
+
+    #[test]
+    fn repeats_start_after_delay() {
+        let mut repeat = KeyRepeat::new();
+        assert_eq!(hold(&mut repeat, 0.29), 0);
+        assert_eq!(hold(&mut repeat, 0.02), 1);
+    }
+
+    #[test]
+    fn repeats_accelerate_up_to_max_rate() {
+        let mut repeat = KeyRepeat::new();
+        hold(&mut repeat, 0.3);
+        let first = hold(&mut repeat, 0.5);
+        hold(&mut repeat, 1.5);
+        let later = hold(&mut repeat, 0.5);
+        assert!(first < later);
+        assert!((14..=16).contains(&later));
+        repeat.update(false, 0.01, 0.3, 30.0);
+        assert_eq!(hold(&mut repeat, 0.2), 0);
+    }
+}
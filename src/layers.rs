@@ -0,0 +1,195 @@
+use std::path::{Path, PathBuf};
+
+use crate::replay::Replay;
+
+// Tints handed out to layers in the order they are loaded. The first layer
+// keeps the plain speed colors, the tint is multiplied with them. Layers
+// not colored by speed use their tint as a flat color.
+const TINTS: [[f32; 3]; 6] = [
+    [1.0, 1.0, 1.0],
+    [1.0, 0.6, 0.2],
+    [0.3, 0.8, 1.0],
+    [0.5, 1.0, 0.4],
+    [1.0, 0.4, 0.9],
+    [1.0, 1.0, 0.3],
+];
+
+// Flat colors of agent groups, picked by group id.
+const GROUP_COLORS: [[f32; 3]; 8] = [
+    [0.30, 0.60, 1.00],
+    [1.00, 0.55, 0.15],
+    [0.35, 0.85, 0.35],
+    [0.90, 0.30, 0.30],
+    [0.70, 0.45, 0.95],
+    [0.95, 0.85, 0.25],
+    [0.30, 0.85, 0.85],
+    [0.95, 0.50, 0.75],
+];
+
+// A trajectory shown as one layer of the scene. All layers play in step
+// with the first one, which acts as the clock for playback and seeking.
+#[derive(Debug)]
+pub struct LoadedTrajectory {
+    pub name: String,
+    pub path: PathBuf,
+    pub replay: Replay,
+    pub tint: [f32; 3],
+    pub color_by_speed: bool,
+    pub visible: bool,
+    // Groups present in the trajectory with their visibility, empty if it
+    // has no groups. Agents are colored by group if `color_by_group` is set.
+    pub groups: Vec<(u32, bool)>,
+    pub color_by_group: bool,
+}
+
+impl LoadedTrajectory {
+    pub fn new(path: &Path, replay: Replay, index: usize) -> Self {
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        );
+        let groups = replay
+            .raw_trajectory()
+            .groups()
+            .into_iter()
+            .map(|group| (group, true))
+            .collect();
+        Self {
+            name,
+            path: path.to_path_buf(),
+            replay,
+            tint: default_tint(index),
+            color_by_speed: true,
+            visible: true,
+            groups,
+            color_by_group: true,
+        }
+    }
+
+    pub fn group_visible(&self, group: u32) -> bool {
+        match self.groups.binary_search_by_key(&group, |(g, _)| *g) {
+            Ok(index) => self.groups[index].1,
+            Err(_) => true,
+        }
+    }
+}
+
+pub fn group_color(group: u32) -> [f32; 3] {
+    GROUP_COLORS[group as usize % GROUP_COLORS.len()]
+}
+
+pub fn default_tint(index: usize) -> [f32; 3] {
+    TINTS[index % TINTS.len()]
+}
+
+// Moves every layer to the time of the first one.
+pub fn sync(layers: &mut [LoadedTrajectory]) {
+    if let Some((clock, others)) = layers.split_first_mut() {
+        for layer in others {
+            layer.replay.sync_to(&clock.replay);
+        }
+    }
+}
+
+// Frame of layer `index` at the time of frame `frame_index` of the first
+// layer.
+pub fn frame_index(layers: &[LoadedTrajectory], index: usize, frame_index: usize) -> usize {
+    if index == 0 {
+        frame_index
+    } else {
+        let time = layers[0].replay.time_of(frame_index);
+        layers[index].replay.frame_at(time)
+    }
+}
+
+// Union of the areas covered by the layers.
+pub fn area(layers: &[LoadedTrajectory]) -> Option<(f32, f32, f32, f32)> {
+    layers
+        .iter()
+        .map(|layer| layer.replay.area())
+        .fold(None, |area, a| match area {
+            Some(b) => Some(union(a, b)),
+            None => Some(a),
+        })
+}
+
+// Agent radius that keeps agents visible without overlapping at the scale
+// of `area`, a small fraction of its diagonal.
+pub fn auto_radius(area: (f32, f32, f32, f32)) -> f32 {
+    const FRACTION: f32 = 0.003;
+    const FALLBACK: f32 = 0.25;
+    let (left, right, bottom, top) = area;
+    let diagonal = (right - left).hypot(top - bottom);
+    if diagonal.is_finite() && diagonal > 0.0 {
+        diagonal * FRACTION
+    } else {
+        FALLBACK
+    }
+}
+
+pub fn union(a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)) -> (f32, f32, f32, f32) {
+    (
+        f32::min(a.0, b.0),
+        f32::max(a.1, b.1),
+        f32::min(a.2, b.2),
+        f32::max(a.3, b.3),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::legacy_parsers::{Frame, Trajectory};
+    use std::time::Duration;
+
+    fn layer(index: usize, positions: Vec<[f32; 2]>) -> LoadedTrajectory {
+        let frames = positions
+            .into_iter()
+            .map(|p| Frame {
+                ids: vec![1],
+                positions: vec![p],
+                time: None,
+                groups: Vec::new(),
+            })
+            .collect();
+        let replay = Replay::new(Trajectory::new(frames, None), Duration::from_millis(100));
+        LoadedTrajectory::new(Path::new("data/run.txt"), replay, index)
+    }
+
+    #[test]
+    fn layers_are_named_and_tinted_in_order() {
+        let first = layer(0, vec![[0.0, 0.0]]);
+        assert_eq!(first.name, "run.txt");
+        assert_eq!(first.tint, [1.0, 1.0, 1.0]);
+        assert_ne!(layer(1, vec![[0.0, 0.0]]).tint, first.tint);
+        assert_eq!(default_tint(TINTS.len()), default_tint(0));
+    }
+
+    #[test]
+    fn hidden_groups_are_looked_up_by_id() {
+        let mut layer = layer(0, vec![[0.0, 0.0]]);
+        assert!(layer.groups.is_empty());
+        layer.groups = vec![(1, true), (4, false)];
+        assert!(layer.group_visible(1));
+        assert!(!layer.group_visible(4));
+        assert!(layer.group_visible(2));
+        assert_eq!(group_color(GROUP_COLORS.len() as u32 + 1), group_color(1));
+    }
+
+    #[test]
+    fn auto_radius_scales_with_the_area() {
+        assert!((auto_radius((0.0, 30.0, 0.0, 40.0)) - 0.15).abs() < 1e-6);
+        assert!((auto_radius((0.0, 3000.0, 0.0, 4000.0)) - 15.0).abs() < 1e-3);
+        assert_eq!(auto_radius((1.0, 1.0, 2.0, 2.0)), 0.25);
+    }
+
+    #[test]
+    fn area_covers_all_layers() {
+        let layers = vec![
+            layer(0, vec![[0.0, 0.0], [1.0, 2.0]]),
+            layer(1, vec![[-3.0, 1.0], [0.5, 0.5]]),
+        ];
+        assert_eq!(area(&layers), Some((-3.0, 1.0, 0.0, 2.0)));
+        assert_eq!(area(&[]), None);
+    }
+}
@@ -1,95 +1,864 @@
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::path::Path;
+use std::path::PathBuf;
 use std::time::Duration;
 
-#[derive(Debug)]
+use crate::builder;
+use crate::builder::TrajectoryBuilder;
+use crate::error::ParseError;
+
+pub const DEFAULT_FPS: f32 = 8.0;
+
+#[derive(Debug, PartialEq)]
 pub struct Trajectory {
     pub frames: Vec<Frame>,
+    pub fps: Option<f32>,
+    // Lines that were neither data, comments nor blank, only counted by the
+    // txt parsers.
+    pub skipped_lines: usize,
+    // Geometry file named in the header, relative paths are relative to
+    // the trajectory file.
+    pub geometry: Option<PathBuf>,
+    // Length unit of the coordinates named in the header, e.g. `m`.
+    pub units: Option<String>,
+    frame_areas: Vec<(f32, f32, f32, f32)>,
+    area: (f32, f32, f32, f32),
 }
 
 impl Trajectory {
+    pub fn new(frames: Vec<Frame>, fps: Option<f32>) -> Self {
+        let frame_areas: Vec<(f32, f32, f32, f32)> = frames.iter().map(Frame::area).collect();
+        let area = frame_areas
+            .iter()
+            .fold((f32::MAX, f32::MIN, f32::MAX, f32::MIN), |a, b| {
+                (
+                    f32::min(a.0, b.0),
+                    f32::max(a.1, b.1),
+                    f32::min(a.2, b.2),
+                    f32::max(a.3, b.3),
+                )
+            });
+        Self {
+            frames,
+            fps,
+            skipped_lines: 0,
+            geometry: None,
+            units: None,
+            frame_areas,
+            area,
+        }
+    }
+
+    // Frame duration according to the file header, or `DEFAULT_FPS` if the
+    // file does not state its frame rate.
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs_f32(1.0 / self.fps.unwrap_or(DEFAULT_FPS))
+    }
+
+    pub fn area(&self) -> (f32, f32, f32, f32) {
+        self.area
+    }
+
+    pub fn frame_area(&self, index: usize) -> (f32, f32, f32, f32) {
+        self.frame_areas[index]
+    }
+
+    pub fn agent_ids(&self) -> HashSet<u32> {
+        self.frames
+            .iter()
+            .flat_map(|f| f.ids.iter().copied())
+            .collect()
+    }
+
+    // Distinct group ids in ascending order, empty if the file has no
+    // group column.
+    pub fn groups(&self) -> Vec<u32> {
+        let mut groups: Vec<u32> = self
+            .frames
+            .iter()
+            .flat_map(|f| f.groups.iter().copied())
+            .collect();
+        groups.sort_unstable();
+        groups.dedup();
+        groups
+    }
+
+    // Copy with every agent's position averaged over up to `window` frames
+    // centered on the current one. The window shrinks near gaps and the
+    // ends of an agent's path so it stays symmetric, which keeps agents
+    // moving at constant velocity in place.
+    pub fn smoothed(&self, window: usize) -> Trajectory {
+        let half = window.saturating_sub(1) / 2;
+        let frames = self
+            .frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                let positions = frame
+                    .ids
+                    .iter()
+                    .zip(&frame.positions)
+                    .map(|(id, p)| {
+                        let mut sum = *p;
+                        let mut count = 1;
+                        for offset in 1..=half {
+                            let before = index
+                                .checked_sub(offset)
+                                .and_then(|i| self.frames[i].position_of(*id));
+                            let after = self
+                                .frames
+                                .get(index + offset)
+                                .and_then(|f| f.position_of(*id));
+                            match (before, after) {
+                                (Some(a), Some(b)) => {
+                                    sum[0] += a[0] + b[0];
+                                    sum[1] += a[1] + b[1];
+                                    count += 2;
+                                }
+                                _ => break,
+                            }
+                        }
+                        [sum[0] / count as f32, sum[1] / count as f32]
+                    })
+                    .collect();
+                Frame {
+                    ids: frame.ids.clone(),
+                    positions,
+                    time: frame.time,
+                    groups: frame.groups.clone(),
+                }
+            })
+            .collect();
+        let mut trajectory = Trajectory::new(frames, self.fps);
+        trajectory.skipped_lines = self.skipped_lines;
+        trajectory.geometry = self.geometry.clone();
+        trajectory.units = self.units.clone();
+        trajectory
+    }
+}
+
+// `ids` and `positions` are parallel arrays sorted by agent id. `time` is
+// the timestamp in seconds for formats that record one per entry. `groups`
+// holds the group of each agent, or is empty if all agents are in group 0.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub ids: Vec<u32>,
+    pub positions: Vec<[f32; 2]>,
+    pub time: Option<f32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<u32>,
+}
+
+impl Frame {
+    pub fn new() -> Self {
+        Self {
+            ids: Vec::new(),
+            positions: Vec::new(),
+            time: None,
+            groups: Vec::new(),
+        }
+    }
+
+    // Group of the agent at `index` in `ids`.
+    pub fn group(&self, index: usize) -> u32 {
+        self.groups.get(index).copied().unwrap_or(0)
+    }
+
     pub fn area(&self) -> (f32, f32, f32, f32) {
         let mut x_min = f32::MAX;
         let mut y_min = f32::MAX;
         let mut x_max = f32::MIN;
         let mut y_max = f32::MIN;
 
-        for f in &self.frames {
-            for p in &f.positions {
-                x_min = f32::min(p[0], x_min);
-                x_max = f32::max(p[0], x_max);
-                y_min = f32::min(p[1], y_min);
-                y_max = f32::max(p[1], y_max);
-            }
+        for p in &self.positions {
+            x_min = f32::min(p[0], x_min);
+            x_max = f32::max(p[0], x_max);
+            y_min = f32::min(p[1], y_min);
+            y_max = f32::max(p[1], y_max);
         }
         (x_min, x_max, y_min, y_max)
     }
+
+    pub fn position_of(&self, id: u32) -> Option<[f32; 2]> {
+        self.ids
+            .binary_search(&id)
+            .ok()
+            .map(|index| self.positions[index])
+    }
+
+    // Id of the agent closest to `position`, only agents within
+    // `max_distance` are considered.
+    pub fn nearest(&self, position: [f32; 2], max_distance: f32) -> Option<u32> {
+        let mut nearest = None;
+        let mut nearest_distance = max_distance * max_distance;
+        for (id, p) in self.ids.iter().zip(&self.positions) {
+            let dx = p[0] - position[0];
+            let dy = p[1] - position[1];
+            let distance = dx * dx + dy * dy;
+            if distance <= nearest_distance {
+                nearest = Some(*id);
+                nearest_distance = distance;
+            }
+        }
+        nearest
+    }
 }
 
 #[derive(Debug)]
-pub struct Frame {
-    pub positions: Vec<[f32; 2]>,
+pub struct Entry {
+    pub agent_id: u32,
+    pub frame_id: i32,
+    pub position: [f32; 2],
+    pub time: Option<f32>,
+    pub group: Option<u32>,
 }
 
-impl Frame {
-    pub fn new() -> Self {
-        Self {
-            positions: Vec::new(),
+// Groups entries into frames, one frame per distinct frame id in ascending
+// order. Within a frame agents are sorted by id. A frame takes the timestamp
+// of its first entry that has one.
+pub fn frames_from_entries(mut entries: Vec<Entry>) -> Vec<Frame> {
+    // Most files are written frame by frame already, checking the order is
+    // a lot cheaper than sorting millions of entries.
+    let key = |e: &Entry| (e.frame_id, e.agent_id);
+    if !entries
+        .windows(2)
+        .all(|pair| key(&pair[0]) <= key(&pair[1]))
+    {
+        entries.sort_by_key(key);
+    }
+    let mut frames = Vec::new();
+    let mut last_frame_id = None;
+    for entry in entries {
+        if last_frame_id != Some(entry.frame_id) {
+            last_frame_id = Some(entry.frame_id);
+            frames.push(Frame::new());
         }
+        let frame: &mut Frame = frames.last_mut().unwrap();
+        frame.ids.push(entry.agent_id);
+        frame.positions.push(entry.position);
+        frame.time = frame.time.or(entry.time);
+        // Formats either have a group for every entry or for none.
+        frame.groups.extend(entry.group);
     }
+    frames
 }
 
-struct Entry {
-    frame_id: i32,
-    position: [f32; 2],
+enum TxtLine {
+    Entry(Entry),
+    Framerate(f32),
+    Geometry(PathBuf),
+    Units(String),
+    Other,
+    Unmatched,
 }
 
-pub fn prase_trajectory_txt(path: &Path) -> (Trajectory, Duration) {
-    let entry_matcher = Regex::new(r"^(\d+)\t(\d+)\t(\d+(?:\.\d+)?)\t(\d+(?:\.\d+)?)").unwrap();
-    let fps_matcher = Regex::new(r"^#framerate: (\d+(?:\.\d+)?)$").unwrap();
-    let file = std::fs::File::open(path).unwrap();
-    let lines = BufReader::new(file).lines();
-    let mut entries = Vec::<Entry>::new();
-    let mut frame_duration_as_f64: f64 = 1.0 / 8.0;
-    for line in lines.flatten() {
-        if let Some(captures) = entry_matcher.captures(line.as_ref()) {
-            let frame_id = captures[2].parse::<i32>().unwrap();
-            let x = captures[3].parse::<f32>().unwrap();
-            let y = captures[4].parse::<f32>().unwrap();
+// Decimal number with optional sign and exponent, e.g. `-1.5` or `2.0e-3`.
+const COORDINATE: &str = r"[-+]?(?:\d+(?:\.\d*)?|\.\d+)(?:[eE][-+]?\d+)?";
+
+struct TxtMatchers {
+    entry: Regex,
+    fps: Regex,
+    geometry: Regex,
+    units: Regex,
+}
+
+impl TxtMatchers {
+    fn new() -> Self {
+        Self {
+            entry: Regex::new(&format!(r"^(\d+)\t(\d+)\t({0})\t({0})", COORDINATE)).unwrap(),
+            fps: Regex::new(r"^#\s*framerate:\s*(\d+(?:\.\d+)?)\s*$").unwrap(),
+            geometry: Regex::new(r"^#\s*geometry:\s*(\S.*?)\s*$").unwrap(),
+            units: Regex::new(r"^#\s*units:\s*(\S.*?)\s*$").unwrap(),
+        }
+    }
+
+    fn parse_line(&self, line: &str, line_number: usize) -> Result<TxtLine, ParseError> {
+        if let Some(captures) = self.entry.captures(line) {
+            let syntax_error = |e: &dyn std::fmt::Display| ParseError::Syntax {
+                line: line_number,
+                message: e.to_string(),
+            };
+            let agent_id = captures[1].parse::<u32>().map_err(|e| syntax_error(&e))?;
+            let frame_id = captures[2].parse::<i32>().map_err(|e| syntax_error(&e))?;
+            let x = captures[3].parse::<f32>().map_err(|e| syntax_error(&e))?;
+            let y = captures[4].parse::<f32>().map_err(|e| syntax_error(&e))?;
             let position = [x, y];
-            entries.push(Entry { frame_id, position })
-        } else if let Some(captures) = fps_matcher.captures(line.as_ref()) {
-            frame_duration_as_f64 = 1.0 / captures[1].parse::<f64>().unwrap();
+            Ok(TxtLine::Entry(Entry {
+                agent_id,
+                frame_id,
+                position,
+                time: None,
+                group: None,
+            }))
+        } else if let Some(captures) = self.fps.captures(line) {
+            match captures[1].parse::<f32>() {
+                Ok(fps) if fps > 0.0 => Ok(TxtLine::Framerate(fps)),
+                _ => Ok(TxtLine::Other),
+            }
+        } else if let Some(captures) = self.geometry.captures(line) {
+            Ok(TxtLine::Geometry(PathBuf::from(&captures[1])))
+        } else if let Some(captures) = self.units.captures(line) {
+            Ok(TxtLine::Units(captures[1].to_string()))
+        } else if line.trim().is_empty() || line.starts_with('#') {
+            Ok(TxtLine::Other)
+        } else {
+            Ok(TxtLine::Unmatched)
         }
     }
-    entries.sort_by(|a, b| a.frame_id.cmp(&b.frame_id));
-    let mut trajectory = Trajectory { frames: Vec::new() };
-    let mut last_index = -1;
-    trajectory.frames.push(Frame::new());
-    for entry in entries {
-        if last_index < entry.frame_id {
-            last_index += 1;
-            trajectory.frames.push(Frame::new());
+}
+
+// Reads a file line by line while keeping track of how much of it has been
+// consumed, which is used to report the parsing progress. The size of
+// streams like stdin is unknown, their progress stays at 0 until the end.
+struct LineReader {
+    reader: Box<dyn BufRead + Send>,
+    line: String,
+    line_number: usize,
+    bytes_read: u64,
+    total_bytes: Option<u64>,
+}
+
+impl LineReader {
+    fn new(reader: Box<dyn BufRead + Send>, total_bytes: Option<u64>) -> Self {
+        Self {
+            reader,
+            line: String::new(),
+            line_number: 0,
+            bytes_read: 0,
+            total_bytes,
         }
-        trajectory
-            .frames
-            .last_mut()
-            .unwrap()
-            .positions
-            .push(entry.position);
     }
-    (trajectory, Duration::from_secs_f64(frame_duration_as_f64))
+
+    fn open(path: &Path) -> Result<Self, ParseError> {
+        let file = std::fs::File::open(path)?;
+        let total_bytes = file.metadata()?.len();
+        Ok(Self::new(Box::new(BufReader::new(file)), Some(total_bytes)))
+    }
+
+    // The next line together with its number, counting from one.
+    fn next_line(&mut self) -> Result<Option<(usize, &str)>, ParseError> {
+        self.line.clear();
+        let bytes = self.reader.read_line(&mut self.line)?;
+        if bytes == 0 {
+            return Ok(None);
+        }
+        self.bytes_read += bytes as u64;
+        self.line_number += 1;
+        // Trimming also takes care of the `\r` of CRLF line endings, which
+        // would otherwise end up in the last field.
+        Ok(Some((self.line_number, self.line.trim())))
+    }
+
+    fn progress(&self) -> f64 {
+        match self.total_bytes {
+            Some(0) => 1.0,
+            Some(total_bytes) => self.bytes_read as f64 / total_bytes as f64,
+            None => 0.0,
+        }
+    }
+}
+
+// `progress` is called with the fraction of the file read so far, roughly
+// once per percent.
+pub fn prase_trajectory_txt(
+    path: &Path,
+    progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    parse_txt_lines(LineReader::open(path)?, progress)
 }
 
+// Same as `prase_trajectory_txt` for any reader, e.g. stdin. Progress is
+// only reported once everything has been read.
+pub fn parse_trajectory_txt_from(
+    reader: impl BufRead + Send + 'static,
+    progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    parse_txt_lines(LineReader::new(Box::new(reader), None), progress)
+}
+
+fn parse_txt_lines(
+    mut reader: LineReader,
+    progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    let matchers = TxtMatchers::new();
+    let mut builder = TrajectoryBuilder::new();
+    let mut fps = None;
+    let mut geometry = None;
+    let mut units = None;
+    let mut skipped_lines = 0;
+    let mut reported = 0.0;
+    while let Some((line_number, line)) = reader.next_line()? {
+        match matchers.parse_line(line, line_number)? {
+            TxtLine::Entry(entry) => builder.push(entry),
+            TxtLine::Framerate(value) => fps = Some(value),
+            TxtLine::Geometry(path) => geometry = Some(path),
+            TxtLine::Units(label) => units = Some(label),
+            TxtLine::Other => {}
+            TxtLine::Unmatched => skipped_lines += 1,
+        }
+        if reader.progress() - reported >= 0.01 {
+            reported = reader.progress();
+            progress(reported);
+        }
+    }
+    progress(1.0);
+    if let Some(fps) = fps {
+        builder = builder.fps(fps);
+    }
+    let mut trajectory = builder.build()?;
+    trajectory.skipped_lines = skipped_lines;
+    trajectory.geometry = geometry;
+    trajectory.units = units;
+    Ok(trajectory)
+}
+
+// Parses a txt trajectory one frame at a time. Unlike `prase_trajectory_txt`
+// this never holds more than a single frame worth of entries, but it
+// requires the file to be ordered by frame id: all entries of a frame have
+// to be consecutive and frames have to appear in ascending order. Files
+// violating this yield an error for the first out of order entry, after
+// which the iteration ends.
+//
+// The frame rate is known once the header has been read, which is the case
+// after the first frame has been yielded.
+pub struct FrameStream {
+    matchers: TxtMatchers,
+    reader: Option<LineReader>,
+    error: Option<ParseError>,
+    // First entry of the next frame with its line number.
+    pending: Option<(usize, Entry)>,
+    last_frame_id: Option<i32>,
+    fps: Option<f32>,
+    geometry: Option<PathBuf>,
+    units: Option<String>,
+    progress: f64,
+    skipped_lines: usize,
+}
+
+impl FrameStream {
+    pub fn fps(&self) -> Option<f32> {
+        self.fps
+    }
+
+    pub fn geometry(&self) -> Option<&Path> {
+        self.geometry.as_deref()
+    }
+
+    pub fn units(&self) -> Option<&str> {
+        self.units.as_deref()
+    }
+
+    // Number of lines so far that were neither data, comments nor blank.
+    pub fn skipped_lines(&self) -> usize {
+        self.skipped_lines
+    }
+
+    // Fraction of the file consumed so far.
+    pub fn progress(&self) -> f64 {
+        self.progress
+    }
+
+    fn fail(&mut self, error: ParseError) -> Option<Result<Frame, ParseError>> {
+        self.reader = None;
+        self.pending = None;
+        Some(Err(error))
+    }
+}
+
+impl Iterator for FrameStream {
+    type Item = Result<Frame, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+        let mut entries = Vec::new();
+        let mut first_line = 0;
+        if let Some((line_number, entry)) = self.pending.take() {
+            first_line = line_number;
+            entries.push(entry);
+        }
+        while let Some(reader) = self.reader.as_mut() {
+            let (line_number, parsed) = match reader.next_line() {
+                Ok(Some((line_number, line))) => {
+                    (line_number, self.matchers.parse_line(line, line_number))
+                }
+                Ok(None) => {
+                    self.progress = 1.0;
+                    self.reader = None;
+                    break;
+                }
+                Err(e) => return self.fail(e),
+            };
+            self.progress = reader.progress();
+            let entry = match parsed {
+                Ok(TxtLine::Entry(entry)) => entry,
+                Ok(TxtLine::Framerate(fps)) => {
+                    self.fps = Some(fps);
+                    continue;
+                }
+                Ok(TxtLine::Geometry(path)) => {
+                    self.geometry = Some(path);
+                    continue;
+                }
+                Ok(TxtLine::Units(label)) => {
+                    self.units = Some(label);
+                    continue;
+                }
+                Ok(TxtLine::Other) => continue,
+                Ok(TxtLine::Unmatched) => {
+                    self.skipped_lines += 1;
+                    continue;
+                }
+                Err(e) => return self.fail(e),
+            };
+            match entries.first() {
+                Some(first) if first.frame_id != entry.frame_id => {
+                    self.pending = Some((line_number, entry));
+                    break;
+                }
+                Some(_) => entries.push(entry),
+                None => {
+                    first_line = line_number;
+                    entries.push(entry);
+                }
+            }
+        }
+        let frame_id = entries.first()?.frame_id;
+        if let Some(last_frame_id) = self.last_frame_id {
+            if frame_id <= last_frame_id {
+                return self.fail(ParseError::Syntax {
+                    line: first_line,
+                    message: format!(
+                        "frame {} follows frame {}, the file is not ordered by frame",
+                        frame_id, last_frame_id
+                    ),
+                });
+            }
+        }
+        self.last_frame_id = Some(frame_id);
+        frames_from_entries(entries).pop().map(Ok)
+    }
+}
+
+pub fn parse_trajectory_streaming(path: &Path) -> FrameStream {
+    let (reader, error) = match LineReader::open(path) {
+        Ok(reader) => (Some(reader), None),
+        Err(e) => (None, Some(e)),
+    };
+    FrameStream {
+        matchers: TxtMatchers::new(),
+        reader,
+        error,
+        pending: None,
+        last_frame_id: None,
+        fps: None,
+        geometry: None,
+        units: None,
+        progress: 0.0,
+        skipped_lines: 0,
+    }
+}
+
+// Builds a trajectory from `parse_trajectory_streaming`, avoiding the
+// intermediate list of all entries. Frames are checked like the builder
+// checks them, so both ways of loading accept the same files.
+pub fn load_trajectory_streaming(
+    path: &Path,
+    progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    let mut stream = parse_trajectory_streaming(path);
+    let mut frames = Vec::new();
+    let mut reported = 0.0;
+    while let Some(frame) = stream.next() {
+        let frame = frame?;
+        builder::validate_frame(&frame, frames.len())?;
+        frames.push(frame);
+        if stream.progress() - reported >= 0.01 {
+            reported = stream.progress();
+            progress(reported);
+        }
+    }
+    progress(1.0);
+    let mut trajectory = Trajectory::new(frames, stream.fps());
+    trajectory.skipped_lines = stream.skipped_lines();
+    trajectory.geometry = stream.geometry().map(Path::to_path_buf);
+    trajectory.units = stream.units().map(str::to_string);
+    Ok(trajectory)
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Write;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn can_parse_from_reader() {
+        let input = b"#framerate: 4\n# units: cm\n1\t0\t1.0\t2.0\n1\t1\t1.5\t2.0\n";
+        let mut progress = Vec::new();
+        let trajectory = parse_trajectory_txt_from(&input[..], &mut |p| progress.push(p)).unwrap();
+        assert_eq!(trajectory.fps, Some(4.0));
+        assert_eq!(trajectory.units.as_deref(), Some("cm"));
+        assert_eq!(trajectory.frames.len(), 2);
+        assert_eq!(progress, vec![1.0]);
+    }
 
     #[test]
     fn can_parse_trivial() {
-        let path = std::path::Path::new("/Users/kkratz/Downloads/11_trains/results/train_traj.txt");
-        let t = prase_trajectory_txt(path);
-        println!("{:?}", t);
+        let mut progress = Vec::new();
+        let trajectory =
+            prase_trajectory_txt(&fixture("trivial.txt"), &mut |p| progress.push(p)).unwrap();
+        assert_eq!(trajectory.fps, Some(10.0));
+        assert_eq!(trajectory.geometry, Some(PathBuf::from("corridor.wkt")));
+        assert_eq!(trajectory.frames.len(), 3);
+        assert_eq!(trajectory.frames[1].ids, vec![1, 2, 3]);
+        assert_eq!(trajectory.frames[2].position_of(3), Some([2.0, 2.1]));
+        assert_eq!(progress.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn crlf_line_endings_are_accepted() {
+        let path = fixture("crlf.txt");
+        let trivial = prase_trajectory_txt(&fixture("trivial.txt"), &mut |_| {}).unwrap();
+        let sorted = prase_trajectory_txt(&path, &mut |_| {}).unwrap();
+        let streamed = load_trajectory_streaming(&path, &mut |_| {}).unwrap();
+        for trajectory in [sorted, streamed].iter() {
+            assert_eq!(trajectory.fps, Some(10.0));
+            assert_eq!(trajectory.geometry, Some(PathBuf::from("corridor.wkt")));
+            assert_eq!(trajectory.skipped_lines, 0);
+            assert_eq!(trajectory.frames, trivial.frames);
+        }
+    }
+
+    #[test]
+    fn coordinates_accept_signs_and_exponents() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(
+            b"# comment\n\n1\t0\t-1.5\t2.0e-3\n2\t0\t+3\t.5\n3\t0\t1E2\t-4.\nnot data\n",
+        )
+        .unwrap();
+        let trajectory = prase_trajectory_txt(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(
+            trajectory.frames[0].positions,
+            vec![[-1.5, 0.002], [3.0, 0.5], [100.0, -4.0]]
+        );
+        assert_eq!(trajectory.skipped_lines, 1);
+        let streamed = load_trajectory_streaming(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(streamed.frames[0].positions, trajectory.frames[0].positions);
+        assert_eq!(streamed.skipped_lines, 1);
+    }
+
+    #[test]
+    fn negative_coordinates_are_parsed() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"1\t0\t-2.5\t1.0\n2\t0\t3.0\t-4.25\n1\t1\t-3.0\t-0.5\n")
+            .unwrap();
+        let trajectory = prase_trajectory_txt(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(trajectory.skipped_lines, 0);
+        assert_eq!(
+            trajectory.frames[0].positions,
+            vec![[-2.5, 1.0], [3.0, -4.25]]
+        );
+        assert_eq!(trajectory.frames[1].position_of(1), Some([-3.0, -0.5]));
+        assert_eq!(trajectory.area(), (-3.0, 3.0, -4.25, 1.0));
+    }
+
+    #[test]
+    fn streaming_matches_sorting_parser() {
+        let path = fixture("trivial.txt");
+        let sorted = prase_trajectory_txt(&path, &mut |_| {}).unwrap();
+        let streamed = load_trajectory_streaming(&path, &mut |_| {}).unwrap();
+        assert_eq!(streamed.fps, sorted.fps);
+        assert_eq!(streamed.frames.len(), sorted.frames.len());
+        for (a, b) in streamed.frames.iter().zip(&sorted.frames) {
+            assert_eq!(a.ids, b.ids);
+            assert_eq!(a.positions, b.positions);
+        }
+    }
+
+    #[test]
+    fn entries_are_grouped_by_frame() {
+        let entry = |agent_id, frame_id, x| Entry {
+            agent_id,
+            frame_id,
+            position: [x, 0.0],
+            time: None,
+            group: None,
+        };
+        let frames = frames_from_entries(vec![
+            entry(2, 5, 2.0),
+            entry(1, 3, 1.0),
+            entry(1, 5, 1.5),
+            entry(3, 3, 3.0),
+        ]);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].ids, vec![1, 3]);
+        assert_eq!(frames[0].positions, vec![[1.0, 0.0], [3.0, 0.0]]);
+        assert_eq!(frames[1].ids, vec![1, 2]);
+        assert_eq!(frames[1].positions, vec![[1.5, 0.0], [2.0, 0.0]]);
+    }
+
+    #[test]
+    fn smoothing_keeps_constant_velocity_and_damps_jitter() {
+        let frames = (0..6)
+            .map(|i| Frame {
+                ids: vec![1, 2],
+                positions: vec![
+                    [i as f32 * 0.5, 1.0],
+                    [0.0, if i % 2 == 0 { 0.1 } else { -0.1 }],
+                ],
+                time: None,
+                groups: Vec::new(),
+            })
+            .collect();
+        let trajectory = Trajectory::new(frames, None);
+        let smoothed = trajectory.smoothed(3);
+        for (raw, smooth) in trajectory.frames.iter().zip(&smoothed.frames) {
+            let (a, b) = (raw.positions[0], smooth.positions[0]);
+            assert!((a[0] - b[0]).abs() < 1e-5 && (a[1] - b[1]).abs() < 1e-5);
+        }
+        let jitter = smoothed.frames[2].positions[1][1];
+        assert!(jitter.abs() < 0.05, "{}", jitter);
+        assert_eq!(smoothed.frames[0].positions[1], [0.0, 0.1]);
+    }
+
+    #[test]
+    fn sorted_entries_match_shuffled_entries() {
+        let entry = |agent_id, frame_id| Entry {
+            agent_id,
+            frame_id,
+            position: [agent_id as f32, frame_id as f32],
+            time: None,
+            group: None,
+        };
+        let keys = [(1, 0), (2, 0), (1, 1), (3, 1), (2, 4)];
+        let sorted = frames_from_entries(keys.iter().map(|(a, f)| entry(*a, *f)).collect());
+        let shuffled = frames_from_entries(
+            [4, 1, 3, 0, 2]
+                .iter()
+                .map(|i| entry(keys[*i].0, keys[*i].1))
+                .collect(),
+        );
+        assert_eq!(sorted.len(), 3);
+        for (a, b) in sorted.iter().zip(&shuffled) {
+            assert_eq!(a.ids, b.ids);
+            assert_eq!(a.positions, b.positions);
+        }
+    }
+
+    #[test]
+    fn agent_ids_are_collected_across_frames() {
+        let trajectory = Trajectory::new(
+            vec![
+                Frame {
+                    ids: vec![1, 2],
+                    positions: vec![[0.0, 0.0], [1.0, 1.0]],
+                    time: None,
+                    groups: Vec::new(),
+                },
+                Frame {
+                    ids: vec![2, 3],
+                    positions: vec![[1.5, 1.5], [2.0, 2.0]],
+                    time: None,
+                    groups: Vec::new(),
+                },
+            ],
+            None,
+        );
+        let ids = trajectory.agent_ids();
+        assert_eq!(ids, [1, 2, 3].iter().copied().collect::<HashSet<u32>>());
+        assert_eq!(trajectory.frames[1].position_of(3), Some([2.0, 2.0]));
+        assert_eq!(trajectory.frames[1].position_of(1), None);
+        assert_eq!(
+            trajectory.frame_duration(),
+            Duration::from_secs_f32(1.0 / DEFAULT_FPS)
+        );
+    }
+
+    #[test]
+    fn nearest_agent_respects_max_distance() {
+        let frame = Frame {
+            ids: vec![4, 9],
+            positions: vec![[0.0, 0.0], [1.0, 0.0]],
+            time: None,
+            groups: Vec::new(),
+        };
+        assert_eq!(frame.nearest([0.8, 0.1], 0.25), Some(9));
+        assert_eq!(frame.nearest([0.2, 0.0], 0.25), Some(4));
+        assert_eq!(frame.nearest([0.5, 0.0], 0.25), None);
+    }
+
+    #[test]
+    fn cached_area_matches_positions() {
+        let trajectory = Trajectory::new(
+            vec![
+                Frame {
+                    ids: vec![1, 2],
+                    positions: vec![[0.0, 3.0], [-1.0, 1.0]],
+                    time: None,
+                    groups: Vec::new(),
+                },
+                Frame {
+                    ids: vec![1],
+                    positions: vec![[4.0, -2.0]],
+                    time: None,
+                    groups: Vec::new(),
+                },
+            ],
+            None,
+        );
+        let positions: Vec<[f32; 2]> = trajectory
+            .frames
+            .iter()
+            .flat_map(|f| f.positions.iter().copied())
+            .collect();
+        let live = (
+            positions.iter().map(|p| p[0]).fold(f32::MAX, f32::min),
+            positions.iter().map(|p| p[0]).fold(f32::MIN, f32::max),
+            positions.iter().map(|p| p[1]).fold(f32::MAX, f32::min),
+            positions.iter().map(|p| p[1]).fold(f32::MIN, f32::max),
+        );
+        assert_eq!(trajectory.area(), live);
+        assert_eq!(trajectory.area(), (-1.0, 4.0, -2.0, 3.0));
+        assert_eq!(trajectory.frame_area(1), (4.0, 4.0, -2.0, -2.0));
+    }
+
+    #[test]
+    fn streaming_detects_unordered_frames() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(
+            b"#framerate: 16.00\n1\t0\t1.0\t1.0\n2\t0\t2.0\t2.0\n1\t1\t1.5\t1.0\n2\t0\t2.5\t2.0\n",
+        )
+        .unwrap();
+        let frames: Vec<_> = parse_trajectory_streaming(file.path()).collect();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].as_ref().unwrap().ids, vec![1, 2]);
+        assert_eq!(frames[1].as_ref().unwrap().ids, vec![1]);
+        assert!(matches!(frames[2], Err(ParseError::Syntax { line: 5, .. })));
+    }
+
+    #[test]
+    fn streaming_rejects_what_the_builder_rejects() {
+        let inputs: [&[u8]; 2] = [
+            b"1\t0\t1.0\t1.0\n1\t0\t2.0\t2.0\n",
+            b"1\t0\t1.0\t1.0\n1\t1\t1e40\t1.0\n",
+        ];
+        for input in inputs.iter() {
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            file.write_all(input).unwrap();
+            assert!(prase_trajectory_txt(file.path(), &mut |_| {}).is_err());
+            assert!(load_trajectory_streaming(file.path(), &mut |_| {}).is_err());
+        }
     }
 }
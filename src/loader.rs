@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+
+use crate::error::ParseError;
+use crate::legacy_parsers::Trajectory;
+use crate::parsers;
+use crate::parsers::CsvLayout;
+
+enum LoadMessage {
+    Progress(f64),
+    Done(Result<Trajectory, ParseError>),
+}
+
+// Files of a folder loaded one after another as separate layers. Files that
+// fail to parse are skipped and reported once the last one has loaded.
+#[derive(Debug, Default)]
+pub struct BatchLoad {
+    pub queue: VecDeque<PathBuf>,
+    pub loaded: usize,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+// Parses a trajectory on a background thread. The main loop polls it once
+// per frame so the UI stays responsive while large files are loaded.
+#[derive(Debug)]
+pub struct Loader {
+    pub path: PathBuf,
+    pub progress: f64,
+    // Layer the result replaces, a new layer is added if `None`.
+    pub layer: Option<usize>,
+    receiver: Receiver<LoadMessage>,
+}
+
+impl Loader {
+    pub fn spawn(path: PathBuf, frame_ordered: bool) -> Self {
+        Self::spawn_with(path, move |path, progress| {
+            if frame_ordered {
+                parsers::parse_trajectory_frame_ordered(path, progress)
+            } else {
+                parsers::parse_trajectory(path, progress)
+            }
+        })
+    }
+
+    // Reads a csv file with the columns picked in the import dialog.
+    pub fn spawn_csv(path: PathBuf, layout: CsvLayout) -> Self {
+        Self::spawn_with(path, move |path, progress| {
+            parsers::parse_trajectory_csv_with(path, &layout, progress)
+        })
+    }
+
+    // Maps the simulation seconds of Viswalk records to frames at `fps`.
+    pub fn spawn_fzp(path: PathBuf, fps: Option<f32>) -> Self {
+        Self::spawn_with(path, move |path, progress| {
+            parsers::parse_trajectory_fzp(path, fps, progress)
+        })
+    }
+
+    fn spawn_with<F>(path: PathBuf, parse: F) -> Self
+    where
+        F: FnOnce(&Path, &mut dyn FnMut(f64)) -> Result<Trajectory, ParseError> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let thread_path = path.clone();
+        std::thread::spawn(move || {
+            let progress_sender = sender.clone();
+            let mut progress = move |fraction| {
+                let _ = progress_sender.send(LoadMessage::Progress(fraction));
+            };
+            let result = parse(&thread_path, &mut progress);
+            let _ = sender.send(LoadMessage::Done(result));
+        });
+        Self {
+            path,
+            progress: 0.0,
+            layer: None,
+            receiver,
+        }
+    }
+
+    // Returns the result once parsing has finished, updating `progress` with
+    // everything reported since the last call.
+    pub fn poll(&mut self) -> Option<Result<Trajectory, ParseError>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(LoadMessage::Progress(fraction)) => self.progress = fraction,
+                Ok(LoadMessage::Done(result)) => return Some(result),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    return Some(Err(ParseError::Io(std::io::Error::other(
+                        "loader thread terminated unexpectedly",
+                    ))))
+                }
+            }
+        }
+    }
+}
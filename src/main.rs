@@ -1,42 +1,75 @@
+mod builder;
+mod camera;
+mod cli;
+mod colormap;
 mod console;
+mod diff;
+mod error;
+mod export;
+mod geometry;
+mod headless;
+mod heatmap;
+mod hotspots;
+mod keybindings;
 mod keymap;
+mod layers;
 mod legacy_parsers;
+mod loader;
+mod measure;
+mod parsers;
+mod projection;
+mod rect;
+mod renderer;
 mod replay;
+mod settings;
+mod validate;
 
+use clap::Parser;
 use glium::glutin::dpi::LogicalSize;
 use glium::glutin::event::{Event, WindowEvent};
 use glium::glutin::event_loop::{ControlFlow, EventLoop};
 use glium::glutin::window::WindowBuilder;
 use glium::glutin::ContextBuilder;
 use glium::{Display, Frame, Surface};
-use imgui::{Condition, Context, Key, MenuItem, Ui, Window};
+use imgui::{Condition, Context, InputTextFlags, MenuItem, StyleColor, Ui, Window};
 use imgui_glium_renderer::Renderer;
 use imgui_winit_support::{HiDpiMode, WinitPlatform};
-use std::time::Duration;
-use winit::window::Fullscreen;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use winit::event::VirtualKeyCode;
 
+use crate::camera::Camera;
+use crate::colormap::Colormap;
 use crate::console::Console;
-use crate::keymap::KeyMap;
+use crate::diff::Divergence;
+use crate::error::{InitError, ParseError};
+use crate::export::AnimationExport;
+use crate::geometry::Geometry;
+use crate::heatmap::Heatmap;
+use crate::keybindings::{Action, KeyCombo};
+use crate::keymap::{KeyMap, KeyRepeat};
+use crate::layers::LoadedTrajectory;
 use crate::legacy_parsers::Trajectory;
+use crate::loader::{BatchLoad, Loader};
+use crate::measure::{Measurement, Units};
+use crate::parsers::CsvLayout;
+use crate::rect::Rect;
+use crate::renderer::Renderer2D;
 use crate::replay::Replay;
+use crate::settings::{DpiMode, Settings};
 
-#[derive(Clone, Copy, Debug)]
-struct Vertex {
-    position: [f32; 3],
-    color: [f32; 3],
-}
-glium::implement_vertex!(Vertex, position, color);
-
-#[derive(Clone, Copy, Debug)]
-struct VertexInstanceAttributes {
-    offset: [f32; 2],
-}
-glium::implement_vertex!(VertexInstanceAttributes, offset);
+// Upper bound for the trajectories loaded by Open Folder, a parameter sweep
+// easily holds more than fit into memory at once.
+const MAX_FOLDER_FILES: usize = 16;
 
 #[derive(Clone, Copy)]
 pub struct Timer {
     last: std::time::Instant,
     pub delta_time: f32,
+    samples: [f32; Timer::SAMPLE_COUNT],
+    next_sample: usize,
+    sample_count: usize,
 }
 
 impl Default for Timer {
@@ -46,10 +79,15 @@ impl Default for Timer {
 }
 
 impl Timer {
+    const SAMPLE_COUNT: usize = 60;
+
     pub fn new() -> Self {
         Self {
             last: std::time::Instant::now(),
             delta_time: 0f32,
+            samples: [0f32; Timer::SAMPLE_COUNT],
+            next_sample: 0,
+            sample_count: 0,
         }
     }
 
@@ -58,13 +96,90 @@ impl Timer {
         let duration = now - self.last;
         self.delta_time = duration.as_secs_f32();
         self.last = now;
+        self.samples[self.next_sample] = self.delta_time;
+        self.next_sample = (self.next_sample + 1) % Timer::SAMPLE_COUNT;
+        self.sample_count = usize::min(self.sample_count + 1, Timer::SAMPLE_COUNT);
+    }
+
+    // Sleeps until at least `1 / max_fps` seconds have passed since the last
+    // `advance`, a `max_fps` of 0 does not wait at all.
+    pub fn limit(&self, max_fps: u32) {
+        if max_fps == 0 {
+            return;
+        }
+        let frame_time = Duration::from_secs_f64(1.0 / max_fps as f64);
+        let elapsed = self.last.elapsed();
+        if elapsed < frame_time {
+            std::thread::sleep(frame_time - elapsed);
+        }
+    }
+
+    // Rolling average over the last `SAMPLE_COUNT` frames.
+    pub fn average_delta_time(&self) -> f32 {
+        if self.sample_count == 0 {
+            return 0.0;
+        }
+        self.samples[..self.sample_count].iter().sum::<f32>() / self.sample_count as f32
     }
 }
 
 #[derive(Debug)]
 pub struct ApplicationState {
-    pub replay: Option<Replay>,
+    // Trajectories shown on top of each other, the first one drives playback.
+    pub layers: Vec<LoadedTrajectory>,
+    pub geometry: Option<Geometry>,
+    // File `geometry` was read from.
+    pub geometry_path: Option<PathBuf>,
+    pub geometry_changed: bool,
     pub console: Console,
+    pub camera: Camera,
+    pub last_directory: Option<PathBuf>,
+    pub window_title: Option<String>,
+    pub export: Option<AnimationExport>,
+    // Frame rate picked in the export menu, the replay's own rate until
+    // changed.
+    pub export_fps: Option<f32>,
+    pub loading: Option<Loader>,
+    // Remaining files of a folder being opened.
+    pub batch: Option<BatchLoad>,
+    // Shown in the loading dialog until it is acknowledged.
+    pub load_error: Option<String>,
+    pub settings: Settings,
+    pub settings_path: Option<PathBuf>,
+    pub show_frame_time: bool,
+    pub average_frame_time: f32,
+    pub selected_id: Option<u32>,
+    // Selected agent the view stays centered on, following stops once the
+    // selection changes.
+    pub follow_agent: Option<u32>,
+    pub heatmap: Heatmap,
+    // Action waiting for a key press in the keybindings window.
+    pub rebinding: Option<Action>,
+    // File currently dragged over the window.
+    pub hovered_file: Option<PathBuf>,
+    // While measuring, clicks place measurement points instead of
+    // selecting agents.
+    pub measuring: bool,
+    pub measurement: Measurement,
+    // Agents outside the region of interest are dimmed or hidden. It is
+    // drawn by dragging with Shift held, starting at `roi_drag_start`.
+    pub roi: Option<Rect>,
+    pub roi_drag_start: Option<[f32; 2]>,
+    // Agents within the region of interest in every frame of the first
+    // layer, together with the region and smoothing window counted for.
+    pub roi_agent_counts: Option<(Rect, usize, Vec<f32>)>,
+    // Set by the `view` command, keeps loading files from moving the view.
+    pub view_locked: bool,
+    // Indices of the two layers whose agent positions are compared.
+    pub diff_layers: Option<(usize, usize)>,
+    // Text of the "Go to frame" field and why it was last rejected.
+    pub goto_frame: String,
+    pub goto_frame_error: Option<(String, Instant)>,
+    // How long the step forward or back keys have been held.
+    pub step_repeat: KeyRepeat,
+    // File shown in the csv import dialog, with its first row and the
+    // columns picked so far.
+    pub csv_import: Option<(PathBuf, Vec<String>, CsvLayout)>,
 }
 
 impl Default for ApplicationState {
@@ -76,8 +191,541 @@ impl Default for ApplicationState {
 impl ApplicationState {
     pub fn new() -> Self {
         Self {
-            replay: None,
+            layers: Vec::new(),
+            geometry: None,
+            geometry_path: None,
+            geometry_changed: false,
             console: Console::new(),
+            camera: Camera::new(),
+            last_directory: None,
+            window_title: None,
+            export: None,
+            export_fps: None,
+            loading: None,
+            batch: None,
+            load_error: None,
+            settings: Settings::new(),
+            settings_path: None,
+            show_frame_time: false,
+            average_frame_time: 0.0,
+            selected_id: None,
+            follow_agent: None,
+            heatmap: Heatmap::new(),
+            rebinding: None,
+            hovered_file: None,
+            measuring: false,
+            measurement: Measurement::new(),
+            roi: None,
+            roi_drag_start: None,
+            roi_agent_counts: None,
+            view_locked: false,
+            diff_layers: None,
+            goto_frame: String::new(),
+            goto_frame_error: None,
+            step_repeat: KeyRepeat::new(),
+            csv_import: None,
+        }
+    }
+
+    // Starts loading the trajectory in the background, it is added as a new
+    // layer once `poll_loading` picks up the result.
+    pub fn open_trajectory(&mut self, path: &Path) {
+        if let Some(loader) = self.loading.as_ref() {
+            self.console.print(&format!(
+                "warning: still loading '{}', '{}' was not opened",
+                loader.path.display(),
+                path.display()
+            ));
+            return;
+        }
+        self.console.print(&format!("Loading '{}'", path.display()));
+        self.loading = Some(self.spawn_loader(path.to_path_buf()));
+    }
+
+    fn spawn_loader(&self, path: PathBuf) -> Loader {
+        if parsers::is_fzp(&path) {
+            let fps = Some(self.settings.fzp_fps).filter(|fps| *fps > 0.0);
+            Loader::spawn_fzp(path, fps)
+        } else {
+            Loader::spawn(path, self.settings.assume_frame_ordered)
+        }
+    }
+
+    // Opens the csv import dialog for `path`, starting with the columns
+    // `CsvLayout::guess` picks.
+    pub fn start_csv_import(&mut self, path: &Path) {
+        let first_row = parsers::sniff_csv_delimiter(path).and_then(|delimiter| {
+            parsers::csv_first_row(path, delimiter).map(|row| (delimiter, row))
+        });
+        match first_row {
+            Ok((delimiter, row)) => {
+                let layout = CsvLayout::guess(&row, delimiter);
+                self.csv_import = Some((path.to_path_buf(), row, layout));
+            }
+            Err(e) => {
+                self.console
+                    .print(&format!("error: cannot read '{}': {}", path.display(), e))
+            }
+        }
+    }
+
+    // Like `open_trajectory`, with the columns picked in the csv import
+    // dialog.
+    pub fn import_csv(&mut self, path: &Path, layout: CsvLayout) {
+        if let Some(loader) = self.loading.as_ref() {
+            self.console.print(&format!(
+                "warning: still loading '{}', '{}' was not opened",
+                loader.path.display(),
+                path.display()
+            ));
+            return;
+        }
+        self.console.print(&format!("Loading '{}'", path.display()));
+        self.loading = Some(Loader::spawn_csv(path.to_path_buf(), layout));
+    }
+
+    // Loads the trajectories in `directory` as layers, at most
+    // `MAX_FOLDER_FILES` of them.
+    pub fn open_folder(&mut self, directory: &Path) {
+        if self.loading.is_some() {
+            self.console.print(&format!(
+                "warning: still loading, '{}' was not opened",
+                directory.display()
+            ));
+            return;
+        }
+        let mut files = match parsers::trajectory_files_in(directory) {
+            Ok(files) => files,
+            Err(e) => {
+                self.console.print(&format!(
+                    "error: cannot read '{}': {}",
+                    directory.display(),
+                    e
+                ));
+                return;
+            }
+        };
+        if files.is_empty() {
+            self.console.print(&format!(
+                "warning: '{}' contains no txt or csv trajectories",
+                directory.display()
+            ));
+            return;
+        }
+        if files.len() > MAX_FOLDER_FILES {
+            self.console.print(&format!(
+                "warning: '{}' contains {} trajectories, only the first {} are loaded",
+                directory.display(),
+                files.len(),
+                MAX_FOLDER_FILES
+            ));
+            files.truncate(MAX_FOLDER_FILES);
+        }
+        self.last_directory = Some(directory.to_path_buf());
+        self.batch = Some(BatchLoad {
+            queue: files.into(),
+            ..BatchLoad::default()
+        });
+        self.load_next_in_batch();
+    }
+
+    // Starts the next file of the batch, or reports the outcome once all
+    // of them have been tried.
+    fn load_next_in_batch(&mut self) {
+        let batch = match self.batch.as_mut() {
+            Some(batch) => batch,
+            None => return,
+        };
+        if let Some(path) = batch.queue.pop_front() {
+            self.open_trajectory(&path);
+            return;
+        }
+        let batch = self.batch.take().unwrap();
+        self.console.print(&format!(
+            "Loaded {} of {} trajectories",
+            batch.loaded,
+            batch.loaded + batch.failed.len()
+        ));
+        for (path, error) in &batch.failed {
+            self.console
+                .print(&format!("warning: skipped '{}': {}", path.display(), error));
+        }
+    }
+
+    pub fn poll_loading(&mut self) {
+        let loader = match self.loading.as_mut() {
+            Some(loader) => loader,
+            None => return,
+        };
+        let previous = loader.progress;
+        match loader.poll() {
+            None => {
+                if (loader.progress * 10.0) as u32 > (previous * 10.0) as u32 {
+                    let percent = loader.progress * 100.0;
+                    self.console.print(&format!("Loading {:.0}%", percent));
+                }
+            }
+            Some(result) => {
+                let path = loader.path.clone();
+                let layer = loader.layer;
+                self.loading = None;
+                match result {
+                    Ok(trajectory) => match layer {
+                        Some(index) => self.replace_trajectory(index, trajectory),
+                        None => {
+                            self.show_trajectory(&path, trajectory);
+                            if let Some(batch) = self.batch.as_mut() {
+                                batch.loaded += 1;
+                            }
+                        }
+                    },
+                    // Failures within a batch are summarized at its end
+                    // instead of interrupting it.
+                    Err(e) if self.batch.is_some() => {
+                        let batch = self.batch.as_mut().unwrap();
+                        batch.failed.push((path, e.to_string()));
+                    }
+                    Err(e) => {
+                        let message = format!("failed to load '{}': {}", path.display(), e);
+                        self.console.print(&format!("error: {}", message));
+                        self.load_error = Some(message);
+                    }
+                }
+                self.load_next_in_batch();
+            }
+        }
+    }
+
+    fn show_trajectory(&mut self, path: &Path, trajectory: Trajectory) {
+        let frame_duration = trajectory.frame_duration();
+        let skipped_lines = trajectory.skipped_lines;
+        let geometry = trajectory.geometry.clone();
+        let replay = Replay::new(trajectory, frame_duration);
+        let (left, right, bottom, top) = replay.area();
+        self.console.print(&format!(
+            "Loaded '{}': {} frames, up to {} agents, area x: [{:.2}, {:.2}] y: [{:.2}, {:.2}]",
+            path.display(),
+            replay.frames(),
+            replay.max_agent_count(),
+            left,
+            right,
+            bottom,
+            top
+        ));
+        if skipped_lines > 0 {
+            self.console.print(&format!(
+                "warning: skipped {} lines that did not look like trajectory data",
+                skipped_lines
+            ));
+        }
+        let mut layer = LoadedTrajectory::new(path, replay, self.layers.len());
+        match self.layers.first() {
+            Some(clock) => layer.replay.sync_to(&clock.replay),
+            None => {
+                self.selected_id = None;
+                self.roi_agent_counts = None;
+                self.heatmap.reset();
+                self.window_title = Some(format!("VisTwo - {}", layer.name));
+                if self.settings.auto_agent_radius {
+                    self.settings.agent_radius = layers::auto_radius(layer.replay.area());
+                }
+            }
+        }
+        self.layers.push(layer);
+        self.fit_view();
+        self.last_directory = path.parent().map(Path::to_path_buf);
+        self.settings.add_recent_file(path);
+        if let Some(geometry) = geometry {
+            let directory = path.parent().unwrap_or_else(|| Path::new(""));
+            self.open_referenced_geometry(&directory.join(geometry));
+        }
+    }
+
+    // Loads the geometry named in a trajectory header, WKT files, jpscore
+    // xml files and JuPedSim databases are supported. Layers of the same
+    // scenario usually share their geometry, it is only read once.
+    fn open_referenced_geometry(&mut self, path: &Path) {
+        if self.geometry_path.as_deref() == Some(path) {
+            return;
+        }
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        let supported = matches!(
+            extension.as_deref(),
+            Some("wkt") | Some("xml") | Some("sqlite") | Some("db")
+        );
+        if !supported {
+            self.console.print(&format!(
+                "warning: ignoring geometry '{}', only WKT and JuPedSim geometry is supported",
+                path.display()
+            ));
+        } else if !path.is_file() {
+            self.console.print(&format!(
+                "warning: geometry '{}' referenced by the trajectory not found, \
+                 use Open Geometry to load it manually",
+                path.display()
+            ));
+        } else if let Err(e) = self.open_geometry(path) {
+            self.console.print(&format!(
+                "error: failed to load '{}': {}",
+                path.display(),
+                e
+            ));
+        } else {
+            self.console
+                .print(&format!("Loaded geometry '{}'", path.display()));
+        }
+    }
+
+    // Parses the file of the first layer again in the background, the
+    // layer is updated in place once it has loaded.
+    pub fn reload(&mut self) -> Result<(), String> {
+        if let Some(loader) = self.loading.as_ref() {
+            return Err(format!("Still loading '{}'", loader.path.display()));
+        }
+        let path = match self.layers.first() {
+            Some(layer) => layer.path.clone(),
+            None => return Err("No trajectory loaded".to_string()),
+        };
+        if parsers::is_stdin(&path) {
+            return Err("Cannot reload a trajectory read from stdin".to_string());
+        }
+        self.console
+            .print(&format!("Reloading '{}'", path.display()));
+        let mut loader = self.spawn_loader(path);
+        loader.layer = Some(0);
+        self.loading = Some(loader);
+        Ok(())
+    }
+
+    // Swaps the trajectory of layer `index` for a freshly loaded one,
+    // staying on the current frame if the new trajectory still has it.
+    fn replace_trajectory(&mut self, index: usize, trajectory: Trajectory) {
+        let layer = match self.layers.get_mut(index) {
+            Some(layer) => layer,
+            None => return,
+        };
+        // The frame rate set in the UI and a clip that still fits survive
+        // the reload.
+        let mut replay = Replay::new(trajectory, layer.replay.frame_duration());
+        let (clip_start, clip_end) = layer.replay.clip();
+        if (clip_start, clip_end) != (0, layer.replay.frames().saturating_sub(1)) {
+            let _ = replay.set_clip(clip_start, clip_end);
+        }
+        replay.seek(layer.replay.current_frame_index);
+        if layer.replay.is_paused() {
+            replay.pause();
+        }
+        let mut reloaded = LoadedTrajectory::new(&layer.path, replay, index);
+        reloaded.tint = layer.tint;
+        reloaded.color_by_speed = layer.color_by_speed;
+        reloaded.visible = layer.visible;
+        reloaded.color_by_group = layer.color_by_group;
+        for (group, visible) in reloaded.groups.iter_mut() {
+            *visible = layer.group_visible(*group);
+        }
+        *layer = reloaded;
+        self.console.print(&format!(
+            "Reloaded '{}': {} frames",
+            layer.path.display(),
+            layer.replay.frames()
+        ));
+        if index == 0 {
+            self.roi_agent_counts = None;
+            self.heatmap.reset();
+            layers::sync(&mut self.layers);
+        }
+    }
+
+    // Units from the settings, falling back to the header of the first
+    // layer's trajectory.
+    pub fn units(&self) -> Units {
+        let header = self
+            .replay()
+            .and_then(|replay| replay.raw_trajectory().units.clone());
+        let label = if self.settings.units.is_empty() {
+            header.unwrap_or_default()
+        } else {
+            self.settings.units.clone()
+        };
+        Units {
+            label,
+            scale: self.settings.unit_scale,
+        }
+    }
+
+    // The first layer, which playback, selection and the heatmap refer to.
+    pub fn replay(&self) -> Option<&Replay> {
+        self.layers.first().map(|layer| &layer.replay)
+    }
+
+    pub fn replay_mut(&mut self) -> Option<&mut Replay> {
+        self.layers.first_mut().map(|layer| &mut layer.replay)
+    }
+
+    pub fn remove_layer(&mut self, index: usize) {
+        if index >= self.layers.len() {
+            return;
+        }
+        let layer = self.layers.remove(index);
+        self.diff_layers = None;
+        self.console
+            .print(&format!("Closed '{}'", layer.path.display()));
+        if index == 0 {
+            self.selected_id = None;
+            self.roi_agent_counts = None;
+            self.heatmap.reset();
+            let title = match self.layers.first() {
+                Some(clock) => format!("VisTwo - {}", clock.name),
+                None => "VisTwo".to_string(),
+            };
+            self.window_title = Some(title);
+            layers::sync(&mut self.layers);
+        }
+    }
+
+    // Opens a file dropped onto the window, wkt files are loaded as geometry
+    // and everything else goes through the trajectory parsers.
+    pub fn open_dropped_file(&mut self, path: &Path) {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        if extension.as_deref() == Some("wkt") {
+            if let Err(e) = self.open_geometry(path) {
+                self.console.print(&format!(
+                    "error: failed to load '{}': {}",
+                    path.display(),
+                    e
+                ));
+            }
+        } else {
+            self.open_trajectory(path);
+        }
+    }
+
+    pub fn open_geometry(&mut self, path: &Path) -> Result<(), ParseError> {
+        self.geometry = Some(parsers::parse_geometry(path)?);
+        self.geometry_path = Some(path.to_path_buf());
+        self.geometry_changed = true;
+        self.fit_view();
+        self.last_directory = path.parent().map(Path::to_path_buf);
+        Ok(())
+    }
+
+    // Bounds of everything that should be visible after a view reset, the
+    // geometry is only taken into account if `fit_view_to_geometry` is set in the settings.
+    pub fn scene_area(&self) -> Option<(f32, f32, f32, f32)> {
+        let replay_area = layers::area(&self.layers);
+        let geometry_area = match self.geometry.as_ref() {
+            Some(geometry) if self.settings.fit_view_to_geometry => Some(geometry.area()),
+            _ => None,
+        };
+        match (replay_area, geometry_area) {
+            (Some(a), Some(b)) => Some(layers::union(a, b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    // Saves the first layer as loaded, i.e. without smoothing.
+    pub fn export_json(&self, path: &Path) -> Result<String, String> {
+        let replay = self.replay().ok_or("No trajectory loaded")?;
+        export::save_trajectory_json(replay.raw_trajectory(), path)?;
+        Ok(format!(
+            "Exported {} frames to '{}'",
+            replay.frames(),
+            path.display()
+        ))
+    }
+
+    pub fn start_export(&mut self, directory: PathBuf, fps: f32) -> Result<(), String> {
+        let replay = self.replay().ok_or("No trajectory loaded")?;
+        if !(fps.is_finite() && fps > 0.0) {
+            return Err(format!("Invalid frame rate {}", fps));
+        }
+        std::fs::create_dir_all(&directory)
+            .map_err(|e| format!("Cannot create '{}': {}", directory.display(), e))?;
+        let export = AnimationExport::new(directory, fps, replay);
+        self.console.print(&format!(
+            "Exporting {} images to '{}'",
+            export.image_count(),
+            export.directory.display()
+        ));
+        self.export = Some(export);
+        Ok(())
+    }
+
+    // A config file that cannot be read is reported and replaced by the
+    // defaults, it gets overwritten when the settings are saved on exit.
+    pub fn load_settings(&mut self) {
+        let path = match Settings::path() {
+            Some(path) => path,
+            None => {
+                self.console
+                    .print("warning: no config directory found, settings will not be saved");
+                return;
+            }
+        };
+        match Settings::load(&path) {
+            Ok(settings) => self.settings = settings,
+            Err(e) => self
+                .console
+                .print(&format!("error: {}, using default settings", e)),
+        }
+        self.settings_path = Some(path);
+    }
+
+    pub fn save_settings(&self) -> Result<(), String> {
+        match self.settings_path.as_ref() {
+            Some(path) => self.settings.save(path),
+            None => Ok(()),
+        }
+    }
+
+    // Selects the agent of the current frame under `position`, or clears
+    // the selection if there is none within the agent radius.
+    pub fn select_at(&mut self, position: [f32; 2]) {
+        let radius = self.settings.agent_radius;
+        self.selected_id = self
+            .replay()
+            .and_then(|replay| replay.current_frame().nearest(position, radius));
+    }
+
+    pub fn toggle_follow_agent(&mut self) {
+        self.follow_agent = match self.follow_agent {
+            Some(_) => None,
+            None => self.selected_id,
+        };
+    }
+
+    // The region of interest, or the one being dragged out at the moment.
+    pub fn visible_roi(&self) -> Option<Rect> {
+        match (self.roi_drag_start, self.camera.cursor_world()) {
+            (Some(start), Some(end)) => Rect::from_corners(start, end),
+            _ => self.roi,
+        }
+    }
+
+    // Fits the view to the scene unless it has been locked to fixed bounds.
+    pub fn fit_view(&mut self) {
+        if !self.view_locked {
+            self.reset_view();
+        }
+    }
+
+    // Shows exactly `bounds`, widened along one axis to match the window.
+    pub fn lock_view(&mut self, bounds: (f32, f32, f32, f32)) {
+        self.camera.follow = false;
+        self.camera.reset(bounds);
+        self.view_locked = true;
+    }
+
+    pub fn reset_view(&mut self) {
+        self.view_locked = false;
+        if let Some(area) = self.scene_area() {
+            let settings = &self.settings;
+            let area = camera::pad_area(area, settings.view_padding, settings.agent_radius);
+            self.camera.reset(area);
         }
     }
 }
@@ -93,14 +741,8 @@ pub struct System {
     pub keymap: KeyMap,
 }
 
-impl Default for System {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl System {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, InitError> {
         let event_loop = EventLoop::new();
 
         // Fix window creation on MacOS, for details see:
@@ -114,29 +756,31 @@ impl System {
             );
         }
 
+        let mut state = ApplicationState::new();
+        state.load_settings();
+
         let wb = WindowBuilder::new()
             //.with_fullscreen(Some(Fullscreen::Borderless(event_loop.primary_monitor())))
             .with_resizable(true)
             .with_inner_size(LogicalSize::new(1024.0, 768.0))
-            .with_title("Hello world");
-        let cb = ContextBuilder::new().with_vsync(true);
-        let display = Display::new(wb, cb, &event_loop).expect("Failed to initialize display!");
+            .with_title("VisTwo");
+        let display = create_display(wb, &event_loop, &mut state)?;
         let mut imgui_ctx = Context::create();
-        imgui_ctx.set_ini_filename(None);
+        imgui_ctx.set_ini_filename(Settings::layout_path());
 
         let mut platform = WinitPlatform::init(&mut imgui_ctx);
         platform.attach_window(
             imgui_ctx.io_mut(),
             display.gl_window().window(),
-            HiDpiMode::Default,
+            hidpi_mode(state.settings.dpi_mode),
         );
-        let renderer =
-            Renderer::init(&mut imgui_ctx, &display).expect("Failed to initialize renderer!");
+        let renderer = Renderer::init(&mut imgui_ctx, &display)?;
         let timer = Timer::new();
-        let state = ApplicationState::new();
+        let (width, height) = display.get_framebuffer_dimensions();
+        state.camera.set_viewport(width, height);
         let keymap = KeyMap::new();
 
-        System {
+        Ok(System {
             display,
             imgui_ctx,
             event_loop,
@@ -145,13 +789,13 @@ impl System {
             timer,
             state,
             keymap,
-        }
+        })
     }
 
     pub fn enter_main_loop<Fn1, Fn2>(self, mut draw_ui: Fn1, mut draw_content: Fn2)
     where
-        Fn1: FnMut(&mut bool, &mut Ui, &mut ApplicationState) + 'static,
-        Fn2: FnMut(&mut Frame, f32, &mut ApplicationState, &Display) + 'static,
+        Fn1: FnMut(&mut bool, &mut Ui, &mut ApplicationState, &KeyMap) + 'static,
+        Fn2: FnMut(&mut Frame, f32, &mut ApplicationState, &Display, &KeyMap) + 'static,
     {
         let Self {
             display,
@@ -165,33 +809,52 @@ impl System {
         } = self;
 
         let mut last_frame = std::time::Instant::now();
+        // Widget sizes are scaled from the unscaled style, scaling the
+        // current one again would compound.
+        let base_style = *imgui_ctx.style();
+        let mut ui_scale = 1.0;
         event_loop.run(move |event, _, control_flow| match event {
             Event::NewEvents(_) => {
+                keymap.begin_frame();
                 let now = std::time::Instant::now();
                 imgui_ctx.io_mut().update_delta_time(now - last_frame);
                 last_frame = now;
             }
             Event::MainEventsCleared => {
+                if !state.settings.vsync {
+                    timer.limit(state.settings.max_fps);
+                }
                 let gl_window = display.gl_window();
                 platform
                     .prepare_frame(imgui_ctx.io_mut(), gl_window.window())
                     .expect("Failed to prepare frame!");
-                //println!("{:?}", &keymap);
                 gl_window.window().request_redraw();
             }
             Event::RedrawRequested(_) => {
+                if state.settings.ui_scale != ui_scale {
+                    ui_scale = state.settings.ui_scale;
+                    let style = imgui_ctx.style_mut();
+                    *style = base_style;
+                    style.scale_all_sizes(ui_scale);
+                    imgui_ctx.io_mut().font_global_scale = ui_scale;
+                }
                 let mut ui = imgui_ctx.frame();
                 let mut keep_running = true;
-                draw_ui(&mut keep_running, &mut ui, &mut state);
+                draw_ui(&mut keep_running, &mut ui, &mut state, &keymap);
                 if !keep_running {
                     *control_flow = ControlFlow::Exit;
                 }
                 let gl_window = display.gl_window();
+                if let Some(title) = state.window_title.take() {
+                    gl_window.window().set_title(&title);
+                }
                 let mut target = display.draw();
-                target.clear_color_srgb(1.0, 0.0, 0.0, 1.0);
+                let [r, g, b, a] = state.settings.background;
+                target.clear_color_srgb(r, g, b, a);
                 platform.prepare_render(&ui, gl_window.window());
                 timer.advance();
-                draw_content(&mut target, timer.delta_time, &mut state, &display);
+                state.average_frame_time = timer.average_delta_time();
+                draw_content(&mut target, timer.delta_time, &mut state, &display, &keymap);
                 let draw_data = ui.render();
                 renderer
                     .render(&mut target, draw_data)
@@ -202,150 +865,514 @@ impl System {
                 event: WindowEvent::CloseRequested,
                 ..
             } => *control_flow = ControlFlow::Exit,
+            // Both sizes are physical pixels, matching the framebuffer the
+            // camera bounds are computed for on HiDPI displays.
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                state.camera.set_viewport(size.width, size.height);
+                let gl_window = display.gl_window();
+                platform.handle_event(imgui_ctx.io_mut(), gl_window.window(), &event);
+                gl_window.window().request_redraw();
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::ScaleFactorChanged {
+                        ref new_inner_size, ..
+                    },
+                ..
+            } => {
+                state
+                    .camera
+                    .set_viewport(new_inner_size.width, new_inner_size.height);
+                let gl_window = display.gl_window();
+                platform.handle_event(imgui_ctx.io_mut(), gl_window.window(), &event);
+                gl_window.window().request_redraw();
+            }
+            Event::WindowEvent {
+                event: WindowEvent::DroppedFile(ref path),
+                ..
+            } => {
+                state.hovered_file = None;
+                state.open_dropped_file(path);
+            }
+            Event::WindowEvent {
+                event: WindowEvent::HoveredFile(ref path),
+                ..
+            } => state.hovered_file = Some(path.clone()),
+            Event::WindowEvent {
+                event: WindowEvent::HoveredFileCancelled,
+                ..
+            } => state.hovered_file = None,
+            Event::LoopDestroyed => {
+                if let Err(e) = state.save_settings() {
+                    eprintln!("Failed to save settings: {}", e);
+                }
+                // imgui only saves the layout periodically and when the
+                // context is dropped, which never happens before exiting.
+                if let Some(path) = imgui_ctx.ini_filename() {
+                    let mut layout = String::new();
+                    imgui_ctx.save_ini_settings(&mut layout);
+                    if let Err(e) = std::fs::write(&path, layout) {
+                        eprintln!("Failed to save window layout: {}", e);
+                    }
+                }
+            }
             event => {
                 keymap.handle_event(&event);
+                if !imgui_ctx.io().want_capture_mouse {
+                    state.camera.handle_event(&event);
+                }
                 platform.handle_event(imgui_ctx.io_mut(), display.gl_window().window(), &event);
             }
         });
     }
 }
 
-fn make_quad() -> Vec<Vertex> {
-    let extend = 1.0;
-    let top_left = [-extend, extend, 0.0];
-    let top_right = [extend, extend, 0.0];
-    let bottom_left = [-extend, -extend, 0.0];
-    let bottom_right = [extend, -extend, 0.0];
-    let red = [1.0, 0.0, 0.0];
-    let green = [0.0, 1.0, 0.0];
-    let blue = [0.0, 0.0, 1.0];
-    let black = [0.0, 0.0, 0.0];
-    vec![
-        Vertex {
-            position: top_left,
-            color: red,
-        },
-        Vertex {
-            position: top_right,
-            color: green,
-        },
-        Vertex {
-            position: bottom_right,
-            color: blue,
-        },
-        Vertex {
-            position: top_left,
-            color: black,
-        },
-        Vertex {
-            position: bottom_right,
-            color: black,
-        },
-        Vertex {
-            position: bottom_left,
-            color: black,
-        },
-    ]
+// Requests the configured number of samples and halves it until the
+// platform accepts the context, the last attempt disables multisampling.
+fn create_display(
+    wb: WindowBuilder,
+    event_loop: &EventLoop<()>,
+    state: &mut ApplicationState,
+) -> Result<Display, InitError> {
+    let mut samples = state.settings.multisampling;
+    loop {
+        let cb = ContextBuilder::new()
+            .with_vsync(state.settings.vsync)
+            .with_multisampling(samples);
+        match Display::new(wb.clone(), cb, event_loop) {
+            Ok(display) => return Ok(display),
+            Err(e) if samples > 0 => {
+                let fallback = if samples > 2 { samples / 2 } else { 0 };
+                state.console.print(&format!(
+                    "warning: {}x multisampling is not available ({}), trying {}x",
+                    samples, e, fallback
+                ));
+                samples = fallback;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
 }
 
-fn main() {
-    let system = System::new();
-    let vertex_buffer = glium::VertexBuffer::new(&system.display, &make_quad()).unwrap();
-
-    let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
-    let vertex_shader_src = r#"
-        #version 140
-
-        in vec3 position;
-        in vec3 color;
-        in vec2 offset;
-        uniform float left;
-        uniform float right;
-        uniform float top;
-        uniform float bottom;
-
-        out vec3 vertex_color;
-
-        mat4 scale(float x, float y, float z) {
-            return mat4(
-                x, 0, 0, 0,
-                0, y, 0, 0,
-                0, 0, z, 0,
-                0, 0, 0, 1
-            );
-        }
+fn hidpi_mode(mode: DpiMode) -> HiDpiMode {
+    match mode {
+        DpiMode::Default => HiDpiMode::Default,
+        DpiMode::Rounded => HiDpiMode::Rounded,
+        DpiMode::Locked => HiDpiMode::Locked(1.0),
+    }
+}
 
-        mat4 trans(vec3 t) {
-            return mat4(
-                  1,   0,   0,   0,
-                  0,   1,   0,   0,
-                  0,   0,   1,   0,
-                t.x, t.y, t.z,   1
-            );
-        }
+fn init() -> Result<(System, Renderer2D), InitError> {
+    let system = System::new()?;
+    let renderer = Renderer2D::new(&system.display)?;
+    Ok((system, renderer))
+}
 
-        mat4 ortho(float left, float right, float top, float bottom, float far, float near) {
-            return mat4(
-                              2.0/(right-left),                            0,                        0, 0,
-                                             0,             2.0/(top-bottom),                        0, 0,
-                                             0,                            0,          -2.0/(far-near), 0,
-                -((right+left) / (right-left)), -((top+bottom)/(top-bottom)), -((far+near)/(far-near)), 1
-            );
+fn main() {
+    let args = cli::Args::parse();
+    if args.bench || !args.render_frames.is_empty() {
+        let result = if args.bench {
+            headless::bench(&args)
+        } else {
+            headless::run(&args)
+        };
+        if let Err(e) = result {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
         }
-
-        mat4 rotZ(float rad) {
-            float sin_rad = sin(rad);
-            float cos_rad = cos(rad);
-            return mat4(
-                cos_rad, -sin_rad, 0.0, 0.0,
-                sin_rad,  cos_rad, 0.0, 0.0,
-                    0.0,      0.0, 1.0, 0.0,
-                    0.0,      0.0, 0.0, 1.0
+        return;
+    }
+    let (mut system, mut renderer) = match init() {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!(
+                "VisTwo needs OpenGL 3.1 or newer, remote desktop sessions and virtual \
+                 machines often only provide an older version."
             );
+            std::process::exit(1);
         }
-
-        void main() {
-            mat4 proj = ortho(left, right, top, bottom, -1.0, 1.0);
-            gl_Position =  proj * trans(vec3(offset, 0.0)) * scale(0.25, 0.25, 0.25) * vec4(position, 1.0);
-            vertex_color = color;
-        }
-    "#;
-    let fragment_shader_src = r#"
-        #version 140
-
-        in vec3 vertex_color;
-        out vec4 frag_color;
-
-        void main() {
-            frag_color = vec4(vertex_color, 1.0);
+    };
+    if let Some(path) = args.geometry.as_ref() {
+        if let Err(e) = system.state.open_geometry(path) {
+            system.state.console.print(&format!(
+                "error: failed to load '{}': {}",
+                path.display(),
+                e
+            ));
         }
-    "#;
-    let program = glium::Program::from_source(
-        &system.display,
-        vertex_shader_src,
-        fragment_shader_src,
-        None,
-    )
-    .unwrap();
+    }
+    if let Some(path) = args.trajectory.as_ref() {
+        system.state.open_trajectory(path);
+    }
+    // Exports walk through the frames on their own, a separate heatmap
+    // keeps them from invalidating the cumulative counts of the live view.
+    let mut export_heatmap = Heatmap::new();
 
     system.enter_main_loop(
-        move |keep_running, ui, state| {
+        move |keep_running, ui, state, keymap| {
+            state.poll_loading();
             let io = ui.io();
+            if state.rebinding.is_some() {
+                capture_rebinding(keymap, state);
+            } else if !io.want_capture_keyboard {
+                handle_shortcuts(keymap, state, keep_running);
+                handle_step_repeat(keymap, state, io.delta_time);
+                handle_camera_keys(keymap, state, io.delta_time);
+            }
+            // Loading another trajectory has to wait for the current one.
+            let idle = state.loading.is_none();
             ui.main_menu_bar(|| {
                 ui.menu("Menu", || {
-                    let file_clicked = MenuItem::new("File").build(ui);
-                    let open_clicked = MenuItem::new("Open").build(ui);
+                    MenuItem::new("File").build(ui);
+                    let open_clicked = MenuItem::new("Open").enabled(idle).build(ui);
                     if open_clicked {
-                        let (trajectory, frame_duration) =
-                            legacy_parsers::prase_trajectory_txt(std::path::Path::new(
-                                "/Users/kkratz/Downloads/results/bottleneck_traj.txt",
-                            ));
-                        state.replay = Some(Replay::new(trajectory, frame_duration));
+                        let mut dialog = rfd::FileDialog::new().add_filter(
+                            "Trajectory",
+                            &["txt", "csv", "json", "sqlite", "db", "h5", "hdf5", "traj", "fzp"],
+                        );
+                        if let Some(directory) = state.last_directory.as_ref() {
+                            dialog = dialog.set_directory(directory);
+                        }
+                        if let Some(path) = dialog.pick_file() {
+                            state.open_trajectory(&path);
+                        }
+                    }
+                    if MenuItem::new("Import CSV").enabled(idle).build(ui) {
+                        let mut dialog =
+                            rfd::FileDialog::new().add_filter("CSV", &["csv", "tsv", "txt"]);
+                        if let Some(directory) = state.last_directory.as_ref() {
+                            dialog = dialog.set_directory(directory);
+                        }
+                        if let Some(path) = dialog.pick_file() {
+                            state.start_csv_import(&path);
+                        }
+                    }
+                    if MenuItem::new("Open Folder").enabled(idle).build(ui) {
+                        let mut dialog = rfd::FileDialog::new();
+                        if let Some(directory) = state.last_directory.as_ref() {
+                            dialog = dialog.set_directory(directory);
+                        }
+                        if let Some(directory) = dialog.pick_folder() {
+                            state.open_folder(&directory);
+                        }
+                    }
+                    ui.menu_with_enabled("Open Recent", idle, || {
+                        let mut clicked = None;
+                        for path in &state.settings.recent_files {
+                            if MenuItem::new(&path.to_string_lossy()).build(ui) {
+                                clicked = Some(path.clone());
+                            }
+                        }
+                        ui.separator();
+                        if MenuItem::new("Clear recent").build(ui) {
+                            state.settings.recent_files.clear();
+                        }
+                        if let Some(path) = clicked {
+                            if path.exists() {
+                                state.open_trajectory(&path);
+                            } else {
+                                state.settings.recent_files.retain(|p| *p != path);
+                                state.console.print(&format!(
+                                    "error: '{}' no longer exists, removed it from the recent files",
+                                    path.display()
+                                ));
+                            }
+                        }
+                    });
+                    let reload_shortcut = state
+                        .settings
+                        .keybindings
+                        .combo(Action::Reload)
+                        .map(|combo| combo.to_string())
+                        .unwrap_or_default();
+                    let reload_clicked = MenuItem::new("Reload")
+                        .shortcut(&reload_shortcut)
+                        .enabled(idle && !state.layers.is_empty())
+                        .build(ui);
+                    if reload_clicked {
+                        if let Err(e) = state.reload() {
+                            state.console.print(&format!("error: {}", e));
+                        }
+                    }
+                    if MenuItem::new("Open Geometry").build(ui) {
+                        let mut dialog = rfd::FileDialog::new()
+                            .add_filter("Geometry", &["wkt", "xml", "txt", "sqlite", "db"]);
+                        if let Some(directory) = state.last_directory.as_ref() {
+                            dialog = dialog.set_directory(directory);
+                        }
+                        if let Some(path) = dialog.pick_file() {
+                            if let Err(e) = state.open_geometry(&path) {
+                                state.console.print(&format!(
+                                    "error: failed to load '{}': {}",
+                                    path.display(),
+                                    e
+                                ));
+                            }
+                        }
+                    }
+                    ui.menu_with_enabled("Export Animation", !state.layers.is_empty(), || {
+                        let replay_fps = state
+                            .replay()
+                            .map_or(0.0, |replay| 1.0 / replay.frame_duration().as_secs_f32());
+                        let mut fps = state.export_fps.unwrap_or(replay_fps);
+                        if imgui::InputFloat::new(ui, "FPS", &mut fps)
+                            .step(1.0)
+                            .build()
+                            && fps.is_finite()
+                            && fps > 0.0
+                        {
+                            state.export_fps = Some(fps);
+                        }
+                        if MenuItem::new("Choose folder...").build(ui) {
+                            if let Some(directory) = rfd::FileDialog::new().pick_folder() {
+                                if let Err(e) = state.start_export(directory, fps) {
+                                    state.console.print(&format!("error: {}", e));
+                                }
+                            }
+                        }
+                        ui.text_disabled("Also available as 'export <dir> <fps>' in the console");
+                    });
+                    if MenuItem::new("Export JSON")
+                        .enabled(!state.layers.is_empty())
+                        .build(ui)
+                    {
+                        let dialog = rfd::FileDialog::new()
+                            .add_filter("JSON", &["json"])
+                            .set_file_name("trajectory.json");
+                        if let Some(path) = dialog.save_file() {
+                            match state.export_json(&path) {
+                                Ok(message) => state.console.print(&message),
+                                Err(e) => state.console.print(&format!("error: {}", e)),
+                            }
+                        }
+                    }
+                    if MenuItem::new("Copy Frame Positions")
+                        .enabled(!state.layers.is_empty())
+                        .build(ui)
+                    {
+                        Console::execute("copy", state);
+                    }
+                    if MenuItem::new("Reset View").build(ui) {
+                        state.reset_view();
+                    }
+                    MenuItem::new("Measure Distances").build_with_ref(ui, &mut state.measuring);
+                    if MenuItem::new("Clear Measurement").build(ui) {
+                        state.measurement.clear();
+                    }
+                    if MenuItem::new("Clear Region of Interest")
+                        .enabled(state.roi.is_some())
+                        .build(ui)
+                    {
+                        state.roi = None;
+                    }
+                    if MenuItem::new("Exit").build(ui) {
+                        *keep_running = false;
                     }
-                    *keep_running = !MenuItem::new("Exit").build(ui);
                 })
             });
-            state.console.draw(&ui);
+            Console::draw(ui, state);
+            draw_csv_import_window(ui, state);
+            draw_loading_modal(ui, state);
+            if state.show_frame_time {
+                let agent_count = state.replay().map(|replay| {
+                    let ids = &replay.current_frame().ids;
+                    let step = state.settings.downsample_step(ids.len());
+                    let shown = ids.iter().filter(|id| *id % step == 0).count();
+                    (shown, ids.len())
+                });
+                let divergence = match (state.diff_layers, state.replay()) {
+                    (Some(pair), Some(replay)) => {
+                        diff::layer_displacements(&state.layers, pair, replay.current_frame_index)
+                            .and_then(|d| diff::divergence(&d))
+                    }
+                    _ => None,
+                };
+                draw_hud_overlay(ui, state.average_frame_time, agent_count, divergence);
+            }
+            if !ui.io().want_capture_mouse {
+                if let Some(position) = state.camera.cursor_world() {
+                    draw_cursor_overlay(ui, position, &state.units());
+                    if ui.is_mouse_clicked(imgui::MouseButton::Left) {
+                        if keymap.shift() {
+                            state.roi_drag_start = Some(position);
+                        } else if state.measuring {
+                            state.measurement.click(position);
+                        } else {
+                            state.select_at(position);
+                        }
+                    }
+                }
+            }
+            if state.roi_drag_start.is_some() && !ui.is_mouse_down(imgui::MouseButton::Left) {
+                state.roi = state.visible_roi();
+                state.roi_drag_start = None;
+            }
+            draw_selection_window(ui, state);
+            let segment = state.measurement.segment(state.camera.cursor_world());
+            if let Some((from, to)) = segment {
+                draw_measurement_label(ui, &state.camera, from, to, &state.units());
+            }
+            let hotspot_threshold = state.settings.hotspot_threshold;
+            if let Some(layer) = state.layers.first_mut() {
+                let goto = (&mut state.goto_frame, &mut state.goto_frame_error);
+                let snap = &mut state.settings.snap_to_frames;
+                draw_timeline_window(ui, &mut layer.replay, hotspot_threshold, snap, goto);
+            }
+            draw_layers_window(ui, state);
+            if state.settings.show_agent_count {
+                draw_agent_count_window(ui, state);
+            }
+            if state.settings.show_minimap {
+                draw_minimap_window(ui, state);
+            }
+            draw_keybindings_window(ui, state);
+            if let Some(path) = state.hovered_file.as_ref() {
+                draw_drop_hint(ui, path);
+            }
+            if let Some(replay) = state.replay() {
+                draw_playback_overlay(ui, replay);
+            }
+            Window::new("Preferences")
+                .size([300.0, 100.0], Condition::FirstUseEver)
+                .build(ui, || {
+                    let settings = &mut state.settings;
+                    imgui::ColorEdit::new("Background", &mut settings.background).build(ui);
+                    multisampling_combo(ui, &mut settings.multisampling);
+                    imgui::Slider::new("UI scale", 0.5, 3.0)
+                        .display_format("%.2fx")
+                        .build(ui, &mut settings.ui_scale);
+                    dpi_mode_combo(ui, &mut settings.dpi_mode);
+                    ui.checkbox("VSync", &mut settings.vsync);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Applies after a restart");
+                    }
+                    if !settings.vsync {
+                        imgui::Slider::new("Frame rate cap", 0, 500)
+                            .display_format("%d fps (0 = uncapped)")
+                            .build(ui, &mut settings.max_fps);
+                    }
+                    // Large scenes get a range that fits their automatic radius.
+                    let auto_area = layers::area(&state.layers);
+                    let max_radius =
+                        auto_area.map_or(1.0, |a| (layers::auto_radius(a) * 4.0).max(1.0));
+                    imgui::Slider::new("Agent radius (m)", 0.01, max_radius)
+                        .build(ui, &mut settings.agent_radius);
+                    if ui.is_item_edited() {
+                        settings.auto_agent_radius = false;
+                    }
+                    ui.same_line();
+                    if ui.small_button("Auto") {
+                        settings.auto_agent_radius = true;
+                        if let Some(area) = auto_area {
+                            settings.agent_radius = layers::auto_radius(area);
+                        }
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Derive the radius from the size of the scene");
+                    }
+                    imgui::Slider::new("Downsample above", 0, 100_000)
+                        .build(ui, &mut settings.downsample_threshold);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Agents per frame, 0 always draws every agent");
+                    }
+                    if settings.downsample_threshold > 0 {
+                        imgui::Slider::new("Draw every Nth agent", 2, 32)
+                            .build(ui, &mut settings.downsample_factor);
+                    }
+                    imgui::Slider::new("Playback speed", 0.1, 10.0)
+                        .build(ui, &mut settings.playback_speed);
+                    imgui::Slider::new("Step repeat delay", 0.1, 1.0)
+                        .display_format("%.2f s")
+                        .build(ui, &mut settings.step_repeat_delay);
+                    imgui::Slider::new("Step repeat rate", 5.0, 120.0)
+                        .display_format("up to %.0f fps")
+                        .build(ui, &mut settings.step_repeat_rate);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Frames per second while a step key is held");
+                    }
+                    imgui::Slider::new("Max speed (m/s)", 0.1, 5.0)
+                        .build(ui, &mut settings.max_speed);
+                    ui.checkbox("Highlight teleports", &mut settings.highlight_teleports);
+                    imgui::InputFloat::new(ui, "Teleport speed (m/s)", &mut settings.teleport_speed)
+                        .step(1.0)
+                        .build();
+                    settings.teleport_speed = settings.teleport_speed.max(0.1);
+                    colormap_combo(ui, "Speed colormap", &mut settings.speed_colormap);
+                    ui.checkbox("Show trails", &mut settings.show_trails);
+                    ui.checkbox("Show headings", &mut settings.show_headings);
+                    imgui::Slider::new("Trail length", 1, 300)
+                        .build(ui, &mut settings.trail_length);
+                    let mut smooth = settings.smoothing_window > 0;
+                    if ui.checkbox("Smooth positions", &mut smooth) {
+                        settings.smoothing_window = if smooth { 5 } else { 0 };
+                    }
+                    if smooth {
+                        imgui::Slider::new("Smoothing window", 3, 31)
+                            .display_format("%d frames")
+                            .build(ui, &mut settings.smoothing_window);
+                    }
+                    imgui::Slider::new("Onion-skin frames", 0, 20)
+                        .build(ui, &mut settings.onion_skin_frames);
+                    imgui::Slider::new("Onion-skin falloff", 0.1, 1.0)
+                        .build(ui, &mut settings.onion_skin_falloff);
+                    ui.input_text("Units", &mut settings.units)
+                        .hint("from trajectory header")
+                        .build();
+                    imgui::InputFloat::new(ui, "Unit scale", &mut settings.unit_scale).build();
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("Coordinates are multiplied by this before they are shown");
+                    }
+                    if settings.unit_scale <= 0.0 {
+                        settings.unit_scale = 1.0;
+                    }
+                    ui.checkbox("Show grid", &mut settings.show_grid);
+                    imgui::InputFloat::new(ui, "Grid spacing (m)", &mut settings.grid_spacing)
+                        .step(0.5)
+                        .build();
+                    settings.grid_spacing = settings.grid_spacing.max(0.01);
+                    ui.checkbox("Show density heatmap", &mut settings.show_heatmap);
+                    imgui::InputFloat::new(ui, "Heatmap cell (m)", &mut settings.heatmap_cell_size)
+                        .step(0.25)
+                        .build();
+                    settings.heatmap_cell_size = settings.heatmap_cell_size.max(0.05);
+                    ui.checkbox("Cumulative heatmap", &mut settings.cumulative_heatmap);
+                    imgui::Slider::new("Hotspot threshold", 0.0, 1.0)
+                        .build(ui, &mut settings.hotspot_threshold);
+                    colormap_combo(ui, "Heatmap colormap", &mut settings.heatmap_colormap);
+                    ui.checkbox(
+                        "Include geometry in view",
+                        &mut settings.fit_view_to_geometry,
+                    );
+                    ui.checkbox("Fill walkable area", &mut settings.fill_walkable_area);
+                    if settings.fill_walkable_area {
+                        imgui::ColorEdit::new("Walkable area", &mut settings.walkable_area_color)
+                            .build(ui);
+                        imgui::Slider::new("Walkable area opacity", 0.0, 1.0)
+                            .display_format("%.2f")
+                            .build(ui, &mut settings.walkable_area_opacity);
+                    }
+                    imgui::Slider::new("View padding", 0.0, 0.5)
+                        .display_format("%.2f")
+                        .build(ui, &mut settings.view_padding);
+                    ui.checkbox(
+                        "Stream frame-ordered files",
+                        &mut settings.assume_frame_ordered,
+                    );
+                    imgui::Slider::new("Viswalk frame rate", 0.0, 50.0)
+                        .display_format("%.1f fps")
+                        .build(ui, &mut settings.fzp_fps);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("0 derives the frame rate from the record interval");
+                    }
+                    ui.checkbox("Hide agents outside region", &mut settings.hide_outside_roi);
+                    ui.checkbox("Show minimap", &mut settings.show_minimap);
+                    ui.checkbox("Show agent count plot", &mut settings.show_agent_count);
+                    ui.checkbox("Follow crowd", &mut state.camera.follow);
+                });
             //if ui.is_key_released(Key::A) {
             //    ui.open_popup("Oh-no");
             //}
@@ -355,69 +1382,854 @@ fn main() {
             //    }
             //};
         },
-        move |target, elapsed, state, display| {
-            let (offsets, (left, right, bottom, top)) = match state.replay.as_mut() {
-                Some(replay) => {
-                    replay.advance_by(Duration::from_secs_f32(elapsed));
-                    let frame = replay.current_frame();
-                    let mut o: Vec<VertexInstanceAttributes> = Vec::new();
-                    o.reserve(frame.positions.len());
-                    for e in &frame.positions {
-                        o.push(VertexInstanceAttributes { offset: *e })
-                    }
-                    (o, replay.area())
-                }
-                None => (Vec::new(), (-1.0, 1.0, -1.0, 1.0)),
+        move |target, elapsed, state, display, _keymap| {
+            let smoothing = state.settings.smoothing_window as usize;
+            for layer in state.layers.iter_mut() {
+                layer.replay.set_smoothing(smoothing);
+            }
+            let playback_speed = state.settings.playback_speed.max(0.0);
+            if let Some(replay) = state.replay_mut() {
+                replay.advance_by(Duration::from_secs_f32(elapsed * playback_speed));
+            }
+            layers::sync(&mut state.layers);
+            if state.geometry_changed {
+                state.geometry_changed = false;
+                renderer.set_geometry(state.geometry.as_ref());
+            }
+            if state.follow_agent != state.selected_id {
+                state.follow_agent = None;
+            }
+            if let Some(id) = state.follow_agent {
+                // Without the agent in the current frame the view stays
+                // where it last saw it.
+                let position = state
+                    .replay()
+                    .and_then(|replay| replay.current_frame().position_of(id));
+                if let Some(position) = position {
+                    state.camera.follow_point(position, elapsed);
+                }
+            } else if state.camera.follow {
+                if let Some(replay) = state.replay() {
+                    let area = replay.frame_area(replay.current_frame_index);
+                    state.camera.follow(area, elapsed);
+                }
+            }
+            let bounds = state.camera.bounds();
+            let frame_index = state
+                .replay()
+                .map_or(0, |replay| replay.current_frame_index);
+            let settings = &state.settings;
+            let heatmap = match state.layers.first() {
+                Some(layer) if settings.show_heatmap => {
+                    state.heatmap.update(
+                        &layer.replay,
+                        frame_index,
+                        settings.heatmap_cell_size,
+                        settings.cumulative_heatmap,
+                    );
+                    Some(&state.heatmap)
+                }
+                _ => None,
             };
-            let offsets2 = vec![
-                VertexInstanceAttributes { offset: [0.0, 0.0] },
-                VertexInstanceAttributes { offset: [0.5, 0.5] },
-                VertexInstanceAttributes { offset: [1.0, 1.0] },
-                VertexInstanceAttributes { offset: [1.5, 1.5] },
-                VertexInstanceAttributes { offset: [2.0, 2.0] },
-                VertexInstanceAttributes { offset: [2.5, 2.5] },
-                VertexInstanceAttributes { offset: [3.0, 3.0] },
-                VertexInstanceAttributes { offset: [3.5, 3.5] },
-            ];
-            let offset_buffer = glium::VertexBuffer::new(display, &offsets).unwrap();
-            //let offset_buffer = glium::VertexBuffer::new(display, &offsets2).unwrap();
-            //let (left, right, bottom, top) = (-1.0f32, 4.5f32, -1.0f32, 4.5f32);
-            let (width, height) = display.get_framebuffer_dimensions();
-            let display_aspect = width as f32 / height as f32;
-            let (left, right, bottom, top) =
-                fixup_aspect_ratio(left, right, bottom, top, display_aspect);
-            target
-                .draw(
-                    (&vertex_buffer, offset_buffer.per_instance().unwrap()),
-                    &indices,
-                    &program,
-                    &glium::uniform! { left: left, right: right, top: top, bottom: bottom },
-                    &Default::default(),
-                )
-                .unwrap();
+            renderer.draw(target, state, frame_index, heatmap, bounds);
+
+            // Exports render one image per main loop iteration so the window
+            // stays responsive and the console can show the progress.
+            if let Some(mut export) = state.export.take() {
+                let next = state.replay().and_then(|replay| export.next(replay));
+                if let Some((image_index, frame_index)) = next {
+                    let size = display.get_framebuffer_dimensions();
+                    let settings = &state.settings;
+                    let heatmap = match state.replay() {
+                        Some(replay) if settings.show_heatmap => {
+                            export_heatmap.update(
+                                replay,
+                                frame_index,
+                                settings.heatmap_cell_size,
+                                settings.cumulative_heatmap,
+                            );
+                            Some(&export_heatmap)
+                        }
+                        _ => None,
+                    };
+                    let texture =
+                        renderer.render_to_texture(state, frame_index, heatmap, bounds, size);
+                    let path = export.image_path(image_index);
+                    if let Err(e) = export::save_png(&texture, &path) {
+                        state.console.print(&format!(
+                            "error: export aborted, cannot write '{}': {}",
+                            path.display(),
+                            e
+                        ));
+                        return;
+                    }
+                    if image_index % 50 == 0 {
+                        state.console.print(&format!(
+                            "Export {:.0}% ({}/{})",
+                            export.progress() * 100.0,
+                            image_index + 1,
+                            export.image_count()
+                        ));
+                    }
+                }
+                if export.is_finished() || state.layers.is_empty() {
+                    state.console.print(&format!(
+                        "Export finished, stitch the images with:\n{}",
+                        export.ffmpeg_command()
+                    ));
+                } else {
+                    state.export = Some(export);
+                }
+            }
         },
     );
 }
 
-fn fixup_aspect_ratio(
-    left: f32,
-    right: f32,
-    bottom: f32,
-    top: f32,
-    display_aspect: f32,
-) -> (f32, f32, f32, f32) {
-    let width = right - left;
-    let height = top - bottom;
-    let data_aspect = width / height;
-    if data_aspect > display_aspect {
-        let desired_height = width / display_aspect;
-        let delta = (desired_height - height) / 2.0;
-        let (left, right, bottom, top) = (left, right, bottom - delta, top + delta);
-        (left, right, bottom, top)
-    } else {
-        let desired_width = height * display_aspect;
-        let delta = (desired_width - width) / 2.0;
-        let (left, right, bottom, top) = (left - delta, right + delta, bottom, top);
-        (left, right, bottom, top)
+fn multisampling_combo(ui: &Ui, samples: &mut u16) {
+    const OPTIONS: [u16; 4] = [0, 2, 4, 8];
+    let names = ["Off", "2x", "4x", "8x"];
+    let mut index = OPTIONS.iter().position(|s| s == samples).unwrap_or(0);
+    if ui.combo_simple_string("Anti-aliasing", &mut index, &names) {
+        *samples = OPTIONS[index];
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Applies after a restart");
+    }
+}
+
+fn dpi_mode_combo(ui: &Ui, mode: &mut DpiMode) {
+    let names: Vec<&str> = DpiMode::ALL.iter().map(|m| m.name()).collect();
+    let mut index = DpiMode::ALL.iter().position(|m| m == mode).unwrap_or(0);
+    if ui.combo_simple_string("Display scaling", &mut index, &names) {
+        *mode = DpiMode::ALL[index];
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Applies after a restart");
+    }
+}
+
+fn colormap_combo(ui: &Ui, label: &str, map: &mut Colormap) {
+    let names: Vec<&str> = Colormap::ALL.iter().map(|m| m.name()).collect();
+    let mut index = Colormap::ALL.iter().position(|m| m == map).unwrap_or(0);
+    if ui.combo_simple_string(label, &mut index, &names) {
+        *map = Colormap::ALL[index];
+    }
+}
+
+fn draw_hud_overlay(
+    ui: &Ui,
+    frame_time: f32,
+    agent_count: Option<(usize, usize)>,
+    divergence: Option<Divergence>,
+) {
+    let display_size = ui.io().display_size;
+    Window::new("HUD")
+        .position([display_size[0] - 10.0, 30.0], Condition::Always)
+        .position_pivot([1.0, 0.0])
+        .no_decoration()
+        .always_auto_resize(true)
+        .save_settings(false)
+        .focus_on_appearing(false)
+        .no_nav()
+        .no_inputs()
+        .bg_alpha(0.35)
+        .build(ui, || {
+            let fps = if frame_time > 0.0 {
+                1.0 / frame_time
+            } else {
+                0.0
+            };
+            ui.text(format!("{:.0} FPS", fps));
+            ui.text(format!("{:.2} ms", frame_time * 1000.0));
+            match agent_count {
+                Some((shown, count)) if shown < count => {
+                    ui.text(format!("showing {} of {} agents", shown, count))
+                }
+                Some((_, count)) => ui.text(format!("{} agents", count)),
+                None => {}
+            }
+            if let Some(divergence) = divergence {
+                ui.text(format!(
+                    "divergence mean {:.3} max {:.3}",
+                    divergence.mean, divergence.max
+                ));
+            }
+        });
+}
+
+fn draw_timeline_window(
+    ui: &Ui,
+    replay: &mut Replay,
+    hotspot_threshold: f32,
+    snap_to_frames: &mut bool,
+    (goto_frame, goto_error): (&mut String, &mut Option<(String, Instant)>),
+) {
+    // How long a rejected frame number keeps the field red.
+    const ERROR_FLASH: Duration = Duration::from_millis(600);
+    Window::new("Timeline")
+        .size([500.0, 80.0], Condition::FirstUseEver)
+        .build(ui, || {
+            let (start, end) = replay.clip();
+            if *snap_to_frames {
+                let mut frame = replay.current_frame_index as u32;
+                if imgui::Slider::new("Frame", start as u32, end as u32).build(ui, &mut frame) {
+                    replay.seek(frame as usize);
+                }
+            } else {
+                let mut time = replay.current_time().as_secs_f32();
+                let slider = imgui::Slider::new(
+                    "Time",
+                    replay.time_of(start).as_secs_f32(),
+                    replay.time_of(end).as_secs_f32(),
+                );
+                if slider.display_format("%.3f s").build(ui, &mut time) {
+                    replay.seek_time(Duration::from_secs_f32(time.max(0.0)));
+                }
+            }
+            ui.same_line();
+            ui.checkbox("Snap", snap_to_frames);
+            if ui.is_item_hovered() {
+                ui.tooltip_text("Scrub by whole frames instead of continuous time");
+            }
+            let flashing = goto_error
+                .as_ref()
+                .is_some_and(|(_, at)| at.elapsed() < ERROR_FLASH);
+            let submitted = {
+                let _color = flashing
+                    .then(|| ui.push_style_color(StyleColor::FrameBg, [0.7, 0.15, 0.15, 1.0]));
+                ui.input_text("Go to frame", goto_frame)
+                    .flags(InputTextFlags::ENTER_RETURNS_TRUE)
+                    .hint("Frame number, Enter to jump")
+                    .build()
+            };
+            if ui.is_item_edited() {
+                *goto_error = None;
+            }
+            if let Some((message, _)) = goto_error.as_ref() {
+                if flashing || ui.is_item_hovered() {
+                    ui.tooltip_text(message);
+                }
+            }
+            if submitted {
+                match replay.parse_frame(goto_frame) {
+                    Ok(frame) => {
+                        replay.seek(frame);
+                        *goto_error = None;
+                    }
+                    Err(message) => *goto_error = Some((message, Instant::now())),
+                }
+            }
+            let last = replay.frames().saturating_sub(1) as i32;
+            let mut clip_start = start as i32;
+            let mut clip_end = end as i32;
+            let changed = imgui::DragRange::new("Clip").range(0, last).build(
+                ui,
+                &mut clip_start,
+                &mut clip_end,
+            );
+            // Invalid ranges while dragging one handle past the other are
+            // ignored, the previous clip stays in place.
+            if changed {
+                let _ = replay.set_clip(clip_start.max(0) as usize, clip_end.max(0) as usize);
+            }
+            if ui.button("< Hotspot") {
+                replay.prev_hotspot(hotspot_threshold);
+            }
+            ui.same_line();
+            if ui.button("Hotspot >") {
+                replay.next_hotspot(hotspot_threshold);
+            }
+            if replay.has_timestamps() {
+                ui.text("Frame times are taken from the file");
+            } else {
+                let mut fps = 1.0 / replay.frame_duration().as_secs_f32();
+                if imgui::InputFloat::new(ui, "FPS", &mut fps)
+                    .step(1.0)
+                    .enter_returns_true(true)
+                    .build()
+                    && fps > 0.0
+                {
+                    replay.set_frame_duration(Duration::from_secs_f32(1.0 / fps));
+                }
+            }
+            ui.text(format!(
+                "Duration: {}",
+                replay::format_timecode(replay.total_time())
+            ));
+        });
+}
+
+fn draw_playback_overlay(ui: &Ui, replay: &Replay) {
+    Window::new("Playback")
+        .position([10.0, 30.0], Condition::Always)
+        .no_decoration()
+        .always_auto_resize(true)
+        .save_settings(false)
+        .focus_on_appearing(false)
+        .no_nav()
+        .no_inputs()
+        .bg_alpha(0.35)
+        .build(ui, || {
+            ui.text(format!(
+                "{} / {}",
+                replay::format_timecode(replay.current_time()),
+                replay::format_timecode(replay.total_time())
+            ));
+            ui.text(format!(
+                "Frame {} / {}{}",
+                replay.current_frame_index,
+                replay.frames().saturating_sub(1),
+                if replay.is_paused() { " (paused)" } else { "" }
+            ));
+        });
+}
+
+// Blocks the rest of the UI while a trajectory loads and reports errors
+// until they are acknowledged.
+fn draw_loading_modal(ui: &Ui, state: &mut ApplicationState) {
+    const TITLE: &str = "Loading";
+    if state.loading.is_some() || state.load_error.is_some() {
+        ui.open_popup(TITLE);
+    }
+    imgui::PopupModal::new(TITLE)
+        .always_auto_resize(true)
+        .build(ui, || {
+            if let Some(loader) = state.loading.as_ref() {
+                ui.text(format!("Loading '{}'", loader.path.display()));
+                imgui::ProgressBar::new(loader.progress as f32)
+                    .size([300.0, 0.0])
+                    .build(ui);
+            } else if let Some(error) = state.load_error.as_ref() {
+                ui.text_colored([1.0, 0.3, 0.3, 1.0], error);
+                if ui.button("OK") {
+                    state.load_error = None;
+                    ui.close_current_popup();
+                }
+            } else {
+                ui.close_current_popup();
+            }
+        });
+}
+
+fn draw_drop_hint(ui: &Ui, path: &Path) {
+    let display_size = ui.io().display_size;
+    Window::new("Drop")
+        .position(
+            [display_size[0] / 2.0, display_size[1] / 2.0],
+            Condition::Always,
+        )
+        .position_pivot([0.5, 0.5])
+        .no_decoration()
+        .always_auto_resize(true)
+        .save_settings(false)
+        .focus_on_appearing(false)
+        .no_nav()
+        .no_inputs()
+        .bg_alpha(0.6)
+        .build(ui, || {
+            let name = path.file_name().unwrap_or(path.as_os_str());
+            ui.text(format!("Drop to open '{}'", name.to_string_lossy()));
+        });
+}
+
+// Shows the measured distance next to the middle of the segment.
+fn draw_measurement_label(ui: &Ui, camera: &Camera, from: [f32; 2], to: [f32; 2], units: &Units) {
+    let middle = [(from[0] + to[0]) / 2.0, (from[1] + to[1]) / 2.0];
+    // The camera works in physical pixels, imgui in logical ones.
+    let screen = camera.world_to_screen(middle);
+    let scale = ui.io().display_framebuffer_scale;
+    Window::new("Measurement")
+        .position(
+            [screen[0] / scale[0], screen[1] / scale[1]],
+            Condition::Always,
+        )
+        .position_pivot([0.5, 1.0])
+        .no_decoration()
+        .always_auto_resize(true)
+        .save_settings(false)
+        .focus_on_appearing(false)
+        .no_nav()
+        .no_inputs()
+        .bg_alpha(0.5)
+        .build(ui, || {
+            ui.text(units.format(measure::distance(from, to)));
+        });
+}
+
+fn draw_cursor_overlay(ui: &Ui, position: [f32; 2], units: &Units) {
+    let display_size = ui.io().display_size;
+    Window::new("Cursor")
+        .position([10.0, display_size[1] - 10.0], Condition::Always)
+        .position_pivot([0.0, 1.0])
+        .no_decoration()
+        .always_auto_resize(true)
+        .save_settings(false)
+        .focus_on_appearing(false)
+        .no_nav()
+        .no_inputs()
+        .bg_alpha(0.35)
+        .build(ui, || {
+            ui.text(format!(
+                "x: {}, y: {}",
+                units.format(position[0]),
+                units.format(position[1])
+            ));
+        });
+}
+
+fn draw_selection_window(ui: &Ui, state: &mut ApplicationState) {
+    let id = match state.selected_id {
+        Some(id) => id,
+        None => return,
+    };
+    let replay = match state.replay() {
+        Some(replay) => replay,
+        None => return,
+    };
+    let frame_index = replay.current_frame_index;
+    let units = state.units();
+    let mut opened = true;
+    let mut following = state.follow_agent == Some(id);
+    let mut follow_toggled = false;
+    Window::new("Selected Agent")
+        .size([220.0, 120.0], Condition::FirstUseEver)
+        .opened(&mut opened)
+        .build(ui, || {
+            ui.text(format!("id: {}", id));
+            follow_toggled = ui.checkbox("Follow", &mut following);
+            let present = replay.current_frame().position_of(id).is_some();
+            if following && !present {
+                ui.same_line();
+                ui.text_disabled("(holding last position)");
+            }
+            match replay.current_frame().position_of(id) {
+                Some(p) => {
+                    ui.text(format!(
+                        "position: x: {}, y: {}",
+                        units.format(p[0]),
+                        units.format(p[1])
+                    ));
+                    match replay.speed_of(frame_index, id) {
+                        Some(speed) if speed >= 0.0 => {
+                            ui.text(format!("speed: {}", units.format_speed(speed)))
+                        }
+                        _ => ui.text("speed: -"),
+                    }
+                }
+                None => ui.text("not present in this frame"),
+            }
+        });
+    if follow_toggled {
+        state.toggle_follow_agent();
+    }
+    if !opened {
+        state.selected_id = None;
+    }
+}
+
+fn draw_layers_window(ui: &Ui, state: &mut ApplicationState) {
+    if state.layers.is_empty() {
+        return;
+    }
+    let mut closed = None;
+    Window::new("Layers")
+        .size([260.0, 120.0], Condition::FirstUseEver)
+        .build(ui, || {
+            for (index, layer) in state.layers.iter_mut().enumerate() {
+                let _id = ui.push_id(index as i32);
+                ui.checkbox("##visible", &mut layer.visible);
+                ui.same_line();
+                imgui::ColorEdit::new("##tint", &mut layer.tint)
+                    .inputs(false)
+                    .build(ui);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Agent color, tints the speed colors while colored by speed");
+                }
+                ui.same_line();
+                ui.checkbox("Speed##color_by_speed", &mut layer.color_by_speed);
+                ui.same_line();
+                ui.text(&layer.name);
+                if index == 0 && ui.is_item_hovered() {
+                    ui.tooltip_text("Drives playback, selection and the heatmap");
+                }
+                ui.same_line();
+                if ui.small_button("Close") {
+                    closed = Some(index);
+                }
+                if !layer.groups.is_empty() {
+                    ui.indent();
+                    ui.checkbox("Color by group", &mut layer.color_by_group);
+                    for (group, visible) in layer.groups.iter_mut() {
+                        let [r, g, b] = layers::group_color(*group);
+                        imgui::ColorButton::new(format!("##group{}", group), [r, g, b, 1.0])
+                            .size([14.0, 14.0])
+                            .build(ui);
+                        ui.same_line();
+                        ui.checkbox(format!("Group {}", group), visible);
+                    }
+                    ui.unindent();
+                }
+            }
+            if state.layers.len() < 2 {
+                return;
+            }
+            ui.separator();
+            let mut enabled = state.diff_layers.is_some();
+            if ui.checkbox("Compare layers", &mut enabled) {
+                state.diff_layers = if enabled { Some((0, 1)) } else { None };
+            }
+            if let Some((a, b)) = state.diff_layers.as_mut() {
+                let names: Vec<&str> = state.layers.iter().map(|l| l.name.as_str()).collect();
+                ui.combo_simple_string("From", a, &names);
+                ui.combo_simple_string("To", b, &names);
+                imgui::Slider::new("Hide below", 0.0, 1.0)
+                    .display_format("%.2f")
+                    .build(ui, &mut state.settings.diff_threshold);
+                imgui::Slider::new("Color scale", 0.1, 10.0)
+                    .display_format("%.1f")
+                    .build(ui, &mut state.settings.diff_scale);
+            }
+        });
+    if let Some(index) = closed {
+        state.remove_layer(index);
+    }
+}
+
+// Agents per frame of the first layer, optionally only those within the
+// region of interest. The current frame is marked, clicking or dragging
+// seeks.
+fn draw_agent_count_window(ui: &Ui, state: &mut ApplicationState) {
+    let roi = state.roi.filter(|_| state.settings.count_in_roi);
+    let replay = match state.layers.first() {
+        Some(layer) => &layer.replay,
+        None => return,
+    };
+    if let Some(roi) = roi {
+        let smoothing = replay.smoothing();
+        let stale = match state.roi_agent_counts.as_ref() {
+            Some((counted_roi, counted_smoothing, _)) => {
+                *counted_roi != roi || *counted_smoothing != smoothing
+            }
+            None => true,
+        };
+        if stale {
+            let counts = replay.agent_counts_within(&roi);
+            state.roi_agent_counts = Some((roi, smoothing, counts));
+        }
+    }
+    let counts = match (roi, state.roi_agent_counts.as_ref()) {
+        (Some(_), Some((_, _, counts))) => counts.as_slice(),
+        _ => replay.agent_counts(),
+    };
+    let mut count_in_roi = state.settings.count_in_roi;
+    let mut seek_to = None;
+    Window::new("Agent Count")
+        .size([400.0, 150.0], Condition::FirstUseEver)
+        .build(ui, || {
+            let frame_index = replay.current_frame_index;
+            let max = counts.iter().copied().fold(1.0, f32::max);
+            let size = [ui.content_region_avail()[0], 80.0];
+            ui.plot_lines("##agent_count", counts)
+                .graph_size(size)
+                .scale_min(0.0)
+                .scale_max(max)
+                .overlay_text(&format!("{} agents", counts[frame_index]))
+                .build();
+            let min = ui.item_rect_min();
+            let width = ui.item_rect_max()[0] - min[0];
+            let last = counts.len().saturating_sub(1).max(1) as f32;
+            let x = min[0] + frame_index as f32 / last * width;
+            ui.get_window_draw_list()
+                .add_line([x, min[1]], [x, min[1] + size[1]], [1.0, 1.0, 0.0, 1.0])
+                .build();
+            if ui.is_item_hovered() && ui.is_mouse_down(imgui::MouseButton::Left) {
+                let t = (ui.io().mouse_pos[0] - min[0]) / width;
+                seek_to = Some((t.clamp(0.0, 1.0) * last).round() as usize);
+            }
+            if state.roi.is_some() || count_in_roi {
+                ui.checkbox("Only in region of interest", &mut count_in_roi);
+            }
+        });
+    state.settings.count_in_roi = count_in_roi;
+    if let Some(frame) = seek_to {
+        if let Some(replay) = state.replay_mut() {
+            replay.seek(frame);
+        }
+    }
+}
+
+// Overview of the whole scene with the agents of the current frame and the
+// part visible in the main view. Clicking or dragging moves the view there.
+fn draw_minimap_window(ui: &Ui, state: &mut ApplicationState) {
+    let area = match state.scene_area() {
+        Some(area) => area,
+        None => return,
+    };
+    Window::new("Minimap")
+        .size([200.0, 200.0], Condition::FirstUseEver)
+        .build(ui, || {
+            let origin = ui.cursor_screen_pos();
+            let size = ui.content_region_avail();
+            if size[0] <= 0.0 || size[1] <= 0.0 {
+                return;
+            }
+            let bounds = projection::fit_aspect_ratio(area, size[0] / size[1]);
+            let to_screen = |world: [f32; 2]| {
+                let screen = projection::world_to_screen(world, bounds, size);
+                [origin[0] + screen[0], origin[1] + screen[1]]
+            };
+            {
+                let draw_list = ui.get_window_draw_list();
+                let (left, right, bottom, top) = area;
+                draw_list
+                    .add_rect(
+                        to_screen([left, top]),
+                        to_screen([right, bottom]),
+                        [0.5, 0.5, 0.5, 1.0],
+                    )
+                    .build();
+                for layer in state.layers.iter().filter(|layer| layer.visible) {
+                    let [r, g, b] = layer.tint;
+                    for p in &layer.replay.current_frame().positions {
+                        draw_list
+                            .add_circle(to_screen(*p), 1.5, [r, g, b, 1.0])
+                            .filled(true)
+                            .build();
+                    }
+                }
+                let (left, right, bottom, top) = state.camera.bounds();
+                draw_list
+                    .add_rect(
+                        to_screen([left, top]),
+                        to_screen([right, bottom]),
+                        [1.0, 1.0, 0.0, 1.0],
+                    )
+                    .build();
+            }
+            ui.invisible_button("minimap", size);
+            if ui.is_item_active() {
+                let mouse = ui.io().mouse_pos;
+                let screen = [mouse[0] - origin[0], mouse[1] - origin[1]];
+                state.camera.center = projection::screen_to_world(screen, bounds, size);
+                state.camera.follow = false;
+            }
+        });
+}
+
+// Lets the user pick the columns of csv files that do not name them the way
+// `CsvLayout::from_headers` expects.
+fn draw_csv_import_window(ui: &Ui, state: &mut ApplicationState) {
+    let (path, first_row, layout) = match state.csv_import.as_mut() {
+        Some(import) => import,
+        None => return,
+    };
+    let mut opened = true;
+    let mut import = false;
+    let mut cancel = false;
+    let mut error = None;
+    Window::new("Import CSV")
+        .opened(&mut opened)
+        .always_auto_resize(true)
+        .collapsible(false)
+        .build(ui, || {
+            ui.text(path.display().to_string());
+            let names: Vec<&str> = parsers::CSV_DELIMITERS.iter().map(|d| d.1).collect();
+            let mut index = parsers::CSV_DELIMITERS
+                .iter()
+                .position(|d| d.0 == layout.delimiter)
+                .unwrap_or(0);
+            if ui.combo_simple_string("Delimiter", &mut index, &names) {
+                layout.delimiter = parsers::CSV_DELIMITERS[index].0;
+                match parsers::csv_first_row(path, layout.delimiter) {
+                    Ok(row) => *first_row = row,
+                    Err(e) => {
+                        error = Some(format!("error: cannot read '{}': {}", path.display(), e))
+                    }
+                }
+            }
+            ui.checkbox("First row is a header", &mut layout.has_headers);
+            // Columns are listed with their value in the first row.
+            let columns: Vec<String> = first_row
+                .iter()
+                .enumerate()
+                .map(|(i, value)| format!("{}: {}", i + 1, value))
+                .collect();
+            ui.combo_simple_string("Agent id", &mut layout.id, &columns);
+            ui.combo_simple_string("Frame", &mut layout.frame, &columns);
+            ui.combo_simple_string("X", &mut layout.x, &columns);
+            ui.combo_simple_string("Y", &mut layout.y, &columns);
+            let optional: Vec<&str> = std::iter::once("(none)")
+                .chain(columns.iter().map(String::as_str))
+                .collect();
+            for (label, column) in [("Time (s)", &mut layout.time), ("Group", &mut layout.group)] {
+                let mut index = column.map_or(0, |c| c + 1);
+                if ui.combo_simple_string(label, &mut index, &optional) {
+                    *column = index.checked_sub(1);
+                }
+            }
+            import = ui.button("Import");
+            ui.same_line();
+            cancel = ui.button("Cancel");
+        });
+    if let Some(message) = error {
+        state.console.print(&message);
+    }
+    if import {
+        if let Some((path, _, layout)) = state.csv_import.take() {
+            state.import_csv(&path, layout);
+        }
+    } else if cancel || !opened {
+        state.csv_import = None;
+    }
+}
+
+fn draw_keybindings_window(ui: &Ui, state: &mut ApplicationState) {
+    Window::new("Keybindings")
+        .size([320.0, 280.0], Condition::FirstUseEver)
+        .collapsed(true, Condition::FirstUseEver)
+        .build(ui, || {
+            for action in Action::ALL.iter().copied() {
+                let label = if state.rebinding == Some(action) {
+                    "press a key...".to_string()
+                } else {
+                    state
+                        .settings
+                        .keybindings
+                        .combo(action)
+                        .map_or("-".to_string(), |combo| combo.to_string())
+                };
+                let _id = ui.push_id(action.name());
+                if ui.button(&label) {
+                    state.rebinding = Some(action);
+                }
+                ui.same_line();
+                ui.text(action.name());
+                let conflicts = state.settings.keybindings.conflicts(action);
+                if !conflicts.is_empty() {
+                    ui.same_line();
+                    ui.text_colored([1.0, 0.3, 0.3, 1.0], "(conflict)");
+                }
+            }
+        });
+}
+
+// Binds the first key pressed while an action waits for a new binding,
+// Escape cancels without changing anything.
+fn capture_rebinding(keymap: &KeyMap, state: &mut ApplicationState) {
+    let action = match state.rebinding {
+        Some(action) => action,
+        None => return,
+    };
+    let key = match keymap
+        .just_pressed_keys()
+        .find(|key| !KeyCombo::is_modifier(*key))
+    {
+        Some(key) => key,
+        None => return,
+    };
+    state.rebinding = None;
+    if key == VirtualKeyCode::Escape {
+        return;
+    }
+    let combo = KeyCombo::held(key, keymap);
+    state.settings.keybindings.bind(action, combo);
+    for other in state.settings.keybindings.conflicts(action) {
+        state.console.print(&format!(
+            "warning: {} is bound to both '{}' and '{}'",
+            combo,
+            action.name(),
+            other.name()
+        ));
+    }
+}
+
+fn handle_shortcuts(keymap: &KeyMap, state: &mut ApplicationState, keep_running: &mut bool) {
+    for action in state.settings.keybindings.triggered(keymap) {
+        apply_action(action, state, keep_running);
+    }
+}
+
+// Keeps stepping while a step key is held, the first step comes from the
+// key press itself.
+fn handle_step_repeat(keymap: &KeyMap, state: &mut ApplicationState, delta_time: f32) {
+    let keybindings = &state.settings.keybindings;
+    let direction = keybindings.is_held(Action::StepForward, keymap) as i64
+        - keybindings.is_held(Action::StepBack, keymap) as i64;
+    let repeats = state.step_repeat.update(
+        direction != 0,
+        delta_time,
+        state.settings.step_repeat_delay,
+        state.settings.step_repeat_rate,
+    );
+    if repeats == 0 {
+        return;
+    }
+    if let Some(replay) = state.replay_mut() {
+        replay.pause();
+        replay.step(direction * repeats as i64);
+    }
+}
+
+// W/A/S/D pan and Q/E zoom while held, unless a modifier is held as well
+// so they do not interfere with shortcuts.
+fn handle_camera_keys(keymap: &KeyMap, state: &mut ApplicationState, delta_time: f32) {
+    if keymap.ctrl() || keymap.alt() || keymap.shift() {
+        return;
+    }
+    let axis = |positive, negative| {
+        keymap.is_pressed(positive) as i32 as f32 - keymap.is_pressed(negative) as i32 as f32
+    };
+    let direction = [
+        axis(VirtualKeyCode::D, VirtualKeyCode::A),
+        axis(VirtualKeyCode::W, VirtualKeyCode::S),
+    ];
+    let zoom = axis(VirtualKeyCode::E, VirtualKeyCode::Q);
+    state.camera.move_by_keys(direction, zoom, delta_time);
+}
+
+fn apply_action(action: Action, state: &mut ApplicationState, keep_running: &mut bool) {
+    match action {
+        Action::Exit => *keep_running = false,
+        Action::ToggleFrameTime => state.show_frame_time = !state.show_frame_time,
+        Action::ResetView => state.reset_view(),
+        Action::ToggleMeasure => state.measuring = !state.measuring,
+        Action::FollowAgent => state.toggle_follow_agent(),
+        Action::Reload => {
+            if let Err(e) = state.reload() {
+                state.console.print(&format!("error: {}", e));
+            }
+        }
+        _ => {}
+    }
+    let replay = match state.replay_mut() {
+        Some(replay) => replay,
+        None => return,
+    };
+    match action {
+        Action::TogglePlay => {
+            if replay.is_paused() {
+                replay.play();
+            } else {
+                replay.pause();
+            }
+        }
+        Action::StepForward | Action::StepBack | Action::JumpForward | Action::JumpBack => {
+            let step = match action {
+                Action::StepForward => 1,
+                Action::StepBack => -1,
+                Action::JumpForward => 10,
+                _ => -10,
+            };
+            replay.pause();
+            replay.step(step);
+        }
+        Action::JumpToStart => replay.seek(0),
+        Action::JumpToEnd => replay.seek(replay.frames().saturating_sub(1)),
+        Action::ResetView
+        | Action::ToggleMeasure
+        | Action::ToggleFrameTime
+        | Action::FollowAgent
+        | Action::Reload
+        | Action::Exit => {}
     }
 }
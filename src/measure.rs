@@ -0,0 +1,111 @@
+// Distance between two points picked in world space. The first click sets
+// the anchor, the second one the end point and a third click starts over.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    anchor: Option<[f32; 2]>,
+    end: Option<[f32; 2]>,
+}
+
+impl Measurement {
+    pub fn new() -> Self {
+        Self {
+            anchor: None,
+            end: None,
+        }
+    }
+
+    pub fn click(&mut self, position: [f32; 2]) {
+        match (self.anchor, self.end) {
+            (Some(_), None) => self.end = Some(position),
+            (Some(_), Some(_)) => *self = Self::new(),
+            (None, _) => self.anchor = Some(position),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn anchor(&self) -> Option<[f32; 2]> {
+        self.anchor
+    }
+
+    // The measured segment, while only the anchor is set it ends at `cursor`
+    // so the distance can be previewed.
+    pub fn segment(&self, cursor: Option<[f32; 2]>) -> Option<([f32; 2], [f32; 2])> {
+        Some((self.anchor?, self.end.or(cursor)?))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.end.is_some()
+    }
+}
+
+// How lengths in trajectory coordinates are shown: multiplied by `scale`
+// and followed by `label`. Without a label bare numbers are shown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Units {
+    pub label: String,
+    pub scale: f32,
+}
+
+impl Units {
+    pub fn format(&self, length: f32) -> String {
+        let length = length * self.scale;
+        if self.label.is_empty() {
+            format!("{:.2}", length)
+        } else {
+            format!("{:.2} {}", length, self.label)
+        }
+    }
+
+    pub fn format_speed(&self, speed: f32) -> String {
+        if self.label.is_empty() {
+            format!("{:.2}", speed * self.scale)
+        } else {
+            format!("{}/s", self.format(speed))
+        }
+    }
+}
+
+pub fn distance(from: [f32; 2], to: [f32; 2]) -> f32 {
+    let dx = to[0] - from[0];
+    let dy = to[1] - from[1];
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn third_click_resets() {
+        let mut measurement = Measurement::new();
+        measurement.click([1.0, 1.0]);
+        assert_eq!(
+            measurement.segment(Some([1.0, 2.0])),
+            Some(([1.0, 1.0], [1.0, 2.0]))
+        );
+        measurement.click([4.0, 5.0]);
+        assert!(measurement.is_complete());
+        let (from, to) = measurement.segment(Some([0.0, 0.0])).unwrap();
+        assert_eq!(distance(from, to), 5.0);
+        measurement.click([7.0, 7.0]);
+        assert_eq!(measurement.segment(None), None);
+        assert_eq!(measurement.anchor(), None);
+    }
+
+    #[test]
+    fn units_are_appended_when_known() {
+        let mut units = Units {
+            label: String::new(),
+            scale: 1.0,
+        };
+        assert_eq!(units.format(12.345), "12.35");
+        assert_eq!(units.format_speed(1.5), "1.50");
+        units.label = "m".to_string();
+        units.scale = 0.01;
+        assert_eq!(units.format(1230.0), "12.30 m");
+        assert_eq!(units.format_speed(150.0), "1.50 m/s");
+    }
+}
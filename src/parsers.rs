@@ -0,0 +1,1144 @@
+use serde::Deserialize;
+use std::io;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::builder;
+use crate::builder::TrajectoryBuilder;
+use crate::error::ParseError;
+use crate::geometry;
+use crate::geometry::Geometry;
+use crate::legacy_parsers;
+use crate::legacy_parsers::Entry;
+use crate::legacy_parsers::Frame;
+use crate::legacy_parsers::Trajectory;
+
+// Path standing for stdin, which is read in the txt format.
+pub const STDIN_PATH: &str = "-";
+
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN_PATH)
+}
+
+pub fn is_fzp(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case("fzp"))
+}
+
+// Trajectory files directly inside `directory`, sorted by name. Only txt
+// and csv files are picked up, other files in the folder are ignored.
+pub fn trajectory_files_in(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        if path.is_file() && matches!(extension.as_deref(), Some("txt") | Some("csv")) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Extensions `parse_trajectory` has a dedicated parser for.
+const NON_TXT_EXTENSIONS: [&str; 8] = ["csv", "json", "sqlite", "db", "h5", "hdf5", "traj", "fzp"];
+
+// Picks the parser based on the file extension, anything that is not a
+// known extension is treated as the legacy txt format. All parsers report
+// the fraction of the input processed so far through `progress`. Files
+// without a single frame are rejected, a replay needs at least one.
+pub fn parse_trajectory(
+    path: &Path,
+    progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    if is_stdin(path) {
+        let stdin = BufReader::new(std::io::stdin());
+        return reject_empty(legacy_parsers::parse_trajectory_txt_from(stdin, progress)?);
+    }
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    let trajectory = match extension.as_deref() {
+        Some("csv") => parse_trajectory_csv(path, progress)?,
+        Some("json") => {
+            let trajectory = parse_trajectory_json(path)?;
+            progress(1.0);
+            trajectory
+        }
+        Some("sqlite") | Some("db") => {
+            let trajectory = parse_trajectory_sqlite(path)?;
+            progress(1.0);
+            trajectory
+        }
+        Some("h5") | Some("hdf5") => parse_trajectory_hdf5(path, progress)?,
+        Some("traj") => parse_trajectory_vadere(path, progress)?,
+        Some("fzp") => parse_trajectory_fzp(path, None, progress)?,
+        _ => legacy_parsers::prase_trajectory_txt(path, progress)?,
+    };
+    reject_empty(trajectory)
+}
+
+// Same as `parse_trajectory`, but txt files are assumed to be ordered by
+// frame which allows streaming them instead of sorting all entries in memory.
+pub fn parse_trajectory_frame_ordered(
+    path: &Path,
+    progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    if is_stdin(path) {
+        return parse_trajectory(path, progress);
+    }
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some(extension) if NON_TXT_EXTENSIONS.contains(&extension) => {
+            parse_trajectory(path, progress)
+        }
+        _ => reject_empty(legacy_parsers::load_trajectory_streaming(path, progress)?),
+    }
+}
+
+// Picks the geometry parser based on the file extension. JuPedSim databases
+// carry the walkable area as WKT next to the trajectory, jpscore uses xml
+// files and anything else is read as a WKT file.
+pub fn parse_geometry(path: &Path) -> Result<Geometry, ParseError> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("sqlite") | Some("db") => parse_geometry_sqlite(path),
+        Some("xml") => geometry::parse_geometry_xml(path),
+        _ => geometry::parse_geometry_wkt(path),
+    }
+}
+
+// Layout of the files written by `export::save_trajectory_json`. The bounds
+// are informational, they are recomputed from the frames when reading.
+#[derive(Deserialize)]
+struct JsonTrajectory {
+    fps: Option<f32>,
+    #[serde(default)]
+    geometry: Option<PathBuf>,
+    #[serde(default)]
+    units: Option<String>,
+    frames: Vec<Frame>,
+}
+
+pub fn parse_trajectory_json(path: &Path) -> Result<Trajectory, ParseError> {
+    let reader = BufReader::new(std::fs::File::open(path)?);
+    let mut json: JsonTrajectory = serde_json::from_reader(reader)?;
+    for (index, frame) in json.frames.iter_mut().enumerate() {
+        let groups_match = frame.groups.is_empty() || frame.groups.len() == frame.ids.len();
+        if frame.ids.len() != frame.positions.len() || !groups_match {
+            return Err(ParseError::Invalid(format!(
+                "frame {} has {} ids but {} positions and {} groups",
+                index,
+                frame.ids.len(),
+                frame.positions.len(),
+                frame.groups.len()
+            )));
+        }
+        sort_by_id(frame);
+        builder::validate_frame(frame, index)?;
+    }
+    let mut trajectory = Trajectory::new(json.frames, json.fps);
+    trajectory.geometry = json.geometry;
+    trajectory.units = json.units;
+    Ok(trajectory)
+}
+
+// Hand-edited files may list the agents of a frame in any order, but
+// lookups by id rely on the ids being sorted.
+fn sort_by_id(frame: &mut Frame) {
+    if frame.ids.windows(2).all(|pair| pair[0] <= pair[1]) {
+        return;
+    }
+    let mut order: Vec<usize> = (0..frame.ids.len()).collect();
+    order.sort_by_key(|&i| frame.ids[i]);
+    frame.ids = order.iter().map(|&i| frame.ids[i]).collect();
+    frame.positions = order.iter().map(|&i| frame.positions[i]).collect();
+    if !frame.groups.is_empty() {
+        frame.groups = order.iter().map(|&i| frame.groups[i]).collect();
+    }
+}
+
+fn reject_empty(trajectory: Trajectory) -> Result<Trajectory, ParseError> {
+    if trajectory.frames.is_empty() {
+        return Err(ParseError::NoFrames);
+    }
+    Ok(trajectory)
+}
+
+// Delimiters `sniff_csv_delimiter` chooses from.
+pub const CSV_DELIMITERS: [(u8, &str); 3] = [(b',', "Comma"), (b';', "Semicolon"), (b'\t', "Tab")];
+
+// Which column of a csv file holds which value, by index. `time` and
+// `group` are optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvLayout {
+    pub delimiter: u8,
+    pub has_headers: bool,
+    pub id: usize,
+    pub frame: usize,
+    pub x: usize,
+    pub y: usize,
+    pub time: Option<usize>,
+    pub group: Option<usize>,
+}
+
+impl CsvLayout {
+    // Looks up the columns `id`, `frame`, `x` and `y` by name, ignoring
+    // case, plus an optional `time` column with timestamps in seconds for
+    // files with variable frame durations and an optional integer `group`
+    // (or `type`) column.
+    pub fn from_headers(headers: &[String], delimiter: u8) -> Result<Self, ParseError> {
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|h| h.eq_ignore_ascii_case(name))
+                .ok_or_else(|| ParseError::Syntax {
+                    line: 1,
+                    message: format!("missing column '{}'", name),
+                })
+        };
+        Ok(Self {
+            delimiter,
+            has_headers: true,
+            id: column("id")?,
+            frame: column("frame")?,
+            x: column("x")?,
+            y: column("y")?,
+            time: column("time").ok(),
+            group: column("group").or_else(|_| column("type")).ok(),
+        })
+    }
+
+    // Starting point of the import dialog for a file whose first row is
+    // `row`. Without the usual column names the columns are assumed in the
+    // order of the txt format, and a row of numbers is taken as data.
+    pub fn guess(row: &[String], delimiter: u8) -> Self {
+        Self::from_headers(row, delimiter).unwrap_or_else(|_| Self {
+            delimiter,
+            has_headers: !row.iter().all(|field| field.parse::<f64>().is_ok()),
+            id: 0,
+            frame: 1,
+            x: 2,
+            y: 3,
+            time: None,
+            group: None,
+        })
+    }
+}
+
+// Picks the delimiter out of `CSV_DELIMITERS` that appears most often in
+// the first line, a comma if none does.
+pub fn sniff_csv_delimiter(path: &Path) -> Result<u8, ParseError> {
+    use std::io::BufRead;
+
+    let mut line = String::new();
+    BufReader::new(std::fs::File::open(path)?).read_line(&mut line)?;
+    let count = |delimiter: u8| line.bytes().filter(|b| *b == delimiter).count();
+    Ok(CSV_DELIMITERS
+        .iter()
+        .map(|(delimiter, _)| *delimiter)
+        .filter(|delimiter| count(*delimiter) > 0)
+        .max_by_key(|delimiter| count(*delimiter))
+        .unwrap_or(b','))
+}
+
+// The fields of the first row, usually the header.
+pub fn csv_first_row(path: &Path, delimiter: u8) -> Result<Vec<String>, ParseError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .trim(csv::Trim::All)
+        .from_path(path)?;
+    let mut record = csv::StringRecord::new();
+    reader.read_record(&mut record)?;
+    Ok(record.iter().map(str::to_string).collect())
+}
+
+// Reads a csv file with a header row naming its columns as described in
+// `CsvLayout::from_headers`. Columns are looked up by name, so their order
+// does not matter and additional columns are ignored.
+pub fn parse_trajectory_csv(
+    path: &Path,
+    progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    let delimiter = sniff_csv_delimiter(path)?;
+    let layout = CsvLayout::from_headers(&csv_first_row(path, delimiter)?, delimiter)?;
+    parse_trajectory_csv_with(path, &layout, progress)
+}
+
+// Reads a csv file with the columns given by `layout`.
+pub fn parse_trajectory_csv_with(
+    path: &Path,
+    layout: &CsvLayout,
+    progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    let total_bytes = std::fs::metadata(path)?.len().max(1) as f64;
+    let mut reported = 0.0;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(layout.delimiter)
+        .has_headers(layout.has_headers)
+        .trim(csv::Trim::All)
+        .from_path(path)?;
+
+    let mut builder = TrajectoryBuilder::new();
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let line = record.position().map_or(0, |p| p.line() as usize);
+        let field = |column: usize| {
+            record.get(column).ok_or_else(|| ParseError::Syntax {
+                line,
+                message: format!("missing field in column {}", column + 1),
+            })
+        };
+        let syntax_error = |e: &dyn std::fmt::Display| ParseError::Syntax {
+            line,
+            message: e.to_string(),
+        };
+        builder.push(Entry {
+            agent_id: field(layout.id)?
+                .parse::<u32>()
+                .map_err(|e| syntax_error(&e))?,
+            frame_id: field(layout.frame)?
+                .parse::<i32>()
+                .map_err(|e| syntax_error(&e))?,
+            position: [
+                field(layout.x)?
+                    .parse::<f32>()
+                    .map_err(|e| syntax_error(&e))?,
+                field(layout.y)?
+                    .parse::<f32>()
+                    .map_err(|e| syntax_error(&e))?,
+            ],
+            time: match layout.time {
+                Some(column) => Some(
+                    field(column)?
+                        .parse::<f32>()
+                        .map_err(|e| syntax_error(&e))?,
+                ),
+                None => None,
+            },
+            group: match layout.group {
+                Some(column) => Some(
+                    field(column)?
+                        .parse::<u32>()
+                        .map_err(|e| syntax_error(&e))?,
+                ),
+                None => None,
+            },
+        });
+        let fraction = reader.position().byte() as f64 / total_bytes;
+        if fraction - reported >= 0.01 {
+            reported = fraction;
+            progress(fraction);
+        }
+    }
+    progress(1.0);
+    builder.build()
+}
+
+// Frame rates of Vadere trajectories. Older versions write positions every
+// simulation step of 0.4 s by default, footsteps of newer versions are
+// sampled at `VADERE_FOOTSTEP_FPS`.
+const VADERE_STEP_FPS: f32 = 2.5;
+const VADERE_FOOTSTEP_FPS: f32 = 10.0;
+
+enum VadereColumns {
+    Steps {
+        step: usize,
+        x: usize,
+        y: usize,
+    },
+    Footsteps {
+        start_time: usize,
+        end_time: usize,
+        start_x: usize,
+        start_y: usize,
+        end_x: usize,
+        end_y: usize,
+    },
+}
+
+// Reads the trajectories written by Vadere, whitespace separated columns
+// named in a header row. The names carry the id of the output processor,
+// e.g. `startX-PID1`, which is ignored. Older versions write a position per
+// `timeStep`, the `postvis.traj` files of newer versions a row per footstep
+// from `startX`/`startY` at `simTime` to `endX`/`endY` at `endTime`.
+pub fn parse_trajectory_vadere(
+    path: &Path,
+    progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    let content = std::fs::read_to_string(path)?;
+    let total_lines = content.lines().count().max(1) as f64;
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+    let (header_index, header) = lines.next().ok_or(ParseError::NoFrames)?;
+    let headers: Vec<&str> = header
+        .split_whitespace()
+        .map(|name| name.split("-PID").next().unwrap_or(name))
+        .collect();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| *h == name)
+            .ok_or_else(|| ParseError::Syntax {
+                line: header_index + 1,
+                message: format!("missing column '{}'", name),
+            })
+    };
+    let id_column = column("pedestrianId")?;
+    let (columns, fps) = match column("timeStep") {
+        Ok(step) => (
+            VadereColumns::Steps {
+                step,
+                x: column("x")?,
+                y: column("y")?,
+            },
+            VADERE_STEP_FPS,
+        ),
+        Err(_) => (
+            VadereColumns::Footsteps {
+                start_time: column("simTime")?,
+                end_time: column("endTime")?,
+                start_x: column("startX")?,
+                start_y: column("startY")?,
+                end_x: column("endX")?,
+                end_y: column("endY")?,
+            },
+            VADERE_FOOTSTEP_FPS,
+        ),
+    };
+
+    let mut builder = TrajectoryBuilder::new().fps(fps);
+    let mut reported = 0.0;
+    for (index, line) in lines {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let syntax_error = |message: String| ParseError::Syntax {
+            line: index + 1,
+            message,
+        };
+        let field = |column: usize| {
+            fields
+                .get(column)
+                .copied()
+                .ok_or_else(|| syntax_error(format!("missing field in column {}", column + 1)))
+        };
+        let number = |column: usize| {
+            let value = field(column)?;
+            value
+                .parse::<f64>()
+                .map_err(|e| syntax_error(format!("invalid number '{}': {}", value, e)))
+        };
+        let agent_id = field(id_column)?
+            .parse::<u32>()
+            .map_err(|e| syntax_error(e.to_string()))?;
+        match columns {
+            VadereColumns::Steps { step, x, y } => builder.push(Entry {
+                agent_id,
+                frame_id: field(step)?
+                    .parse::<i32>()
+                    .map_err(|e| syntax_error(e.to_string()))?,
+                position: [number(x)? as f32, number(y)? as f32],
+                time: None,
+                group: None,
+            }),
+            VadereColumns::Footsteps {
+                start_time,
+                end_time,
+                start_x,
+                start_y,
+                end_x,
+                end_y,
+            } => {
+                let (start, end) = (number(start_time)?, number(end_time)?);
+                let from = [number(start_x)?, number(start_y)?];
+                let to = [number(end_x)?, number(end_y)?];
+                // Every frame within [start, end) lies on this footstep, the
+                // next footstep of the agent starts where it ends.
+                let mut frame = (start * fps as f64).ceil() as i32;
+                while (frame as f64) / (fps as f64) < end {
+                    let s = ((frame as f64) / (fps as f64) - start) / (end - start);
+                    builder.push(Entry {
+                        agent_id,
+                        frame_id: frame,
+                        position: [
+                            (from[0] + (to[0] - from[0]) * s) as f32,
+                            (from[1] + (to[1] - from[1]) * s) as f32,
+                        ],
+                        time: None,
+                        group: None,
+                    });
+                    frame += 1;
+                }
+            }
+        }
+        let fraction = index as f64 / total_lines;
+        if fraction - reported >= 0.01 {
+            reported = fraction;
+            progress(fraction);
+        }
+    }
+    progress(1.0);
+    builder.build()
+}
+
+// Indices of the .fzp columns named by the `$PEDESTRIAN:` line.
+struct FzpColumns {
+    seconds: usize,
+    agent_id: usize,
+    x: usize,
+    y: usize,
+    group: Option<usize>,
+}
+
+impl FzpColumns {
+    fn from_headers(headers: &[String]) -> Result<Self, String> {
+        let column = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| headers.iter().position(|h| h == name))
+                .ok_or_else(|| format!("missing column '{}'", names[0]))
+        };
+        Ok(Self {
+            seconds: column(&["SIMSEC"])?,
+            agent_id: column(&["NO"])?,
+            x: column(&["COORDCENTX", "COORDFRONTX"])?,
+            y: column(&["COORDCENTY", "COORDFRONTY"])?,
+            group: column(&["PEDTYPE"]).ok(),
+        })
+    }
+}
+
+// Reads the pedestrian records Viswalk writes to .fzp files. Lines starting
+// with `*` are comments, the `$PEDESTRIAN:` line names the `;` separated
+// columns. Positions come from `COORDCENTX`/`COORDCENTY` (or the front of
+// the pedestrian if the center is missing) at `SIMSEC`, which is mapped to
+// frames at `fps`. Without `fps` the shortest interval between two records
+// is used. The optional `PEDTYPE` column becomes the group.
+pub fn parse_trajectory_fzp(
+    path: &Path,
+    fps: Option<f32>,
+    progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    let content = std::fs::read_to_string(path)?;
+    let total_lines = content.lines().count().max(1) as f64;
+    let mut columns: Option<FzpColumns> = None;
+    // Seconds, agent id, position and group of every record.
+    let mut records: Vec<(f64, u32, [f32; 2], Option<u32>)> = Vec::new();
+    let mut reported = 0.0;
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let syntax_error = |message: String| ParseError::Syntax {
+            line: index + 1,
+            message,
+        };
+        if line.is_empty() || line.starts_with('*') {
+            continue;
+        }
+        if let Some(directive) = line.strip_prefix('$') {
+            if let Some(names) = directive.strip_prefix("PEDESTRIAN:") {
+                let headers: Vec<String> = names
+                    .split(';')
+                    .map(|name| name.trim().to_ascii_uppercase())
+                    .collect();
+                columns = Some(FzpColumns::from_headers(&headers).map_err(syntax_error)?);
+            }
+            continue;
+        }
+        let columns = columns
+            .as_ref()
+            .ok_or_else(|| syntax_error("record before the $PEDESTRIAN: line".to_string()))?;
+        let fields: Vec<&str> = line.split(';').map(str::trim).collect();
+        let field = |column: usize| {
+            fields
+                .get(column)
+                .copied()
+                .ok_or_else(|| syntax_error(format!("missing field in column {}", column + 1)))
+        };
+        let number = |column: usize| {
+            let value = field(column)?;
+            value
+                .parse::<f64>()
+                .map_err(|e| syntax_error(format!("invalid number '{}': {}", value, e)))
+        };
+        let group = match columns.group {
+            Some(column) => Some(
+                field(column)?
+                    .parse::<u32>()
+                    .map_err(|e| syntax_error(e.to_string()))?,
+            ),
+            None => None,
+        };
+        records.push((
+            number(columns.seconds)?,
+            field(columns.agent_id)?
+                .parse::<u32>()
+                .map_err(|e| syntax_error(e.to_string()))?,
+            [number(columns.x)? as f32, number(columns.y)? as f32],
+            group,
+        ));
+        let fraction = index as f64 / total_lines;
+        if fraction - reported >= 0.01 {
+            reported = fraction;
+            progress(fraction);
+        }
+    }
+
+    let fps = match fps {
+        Some(fps) => fps as f64,
+        None => {
+            let mut times: Vec<f64> = records.iter().map(|r| r.0).collect();
+            times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            times
+                .windows(2)
+                .map(|pair| pair[1] - pair[0])
+                .filter(|interval| *interval > 1e-6)
+                .fold(None, |shortest: Option<f64>, interval| {
+                    Some(shortest.map_or(interval, |s| s.min(interval)))
+                })
+                .map_or(1.0, |interval| 1.0 / interval)
+        }
+    };
+    // Records keep their timestamps, so frames the rate leaves empty do not
+    // speed up playback. If an agent has several records in one frame, the
+    // one nearest to the frame time is kept.
+    let mut entries: Vec<(f64, Entry)> = records
+        .into_iter()
+        .map(|(seconds, agent_id, position, group)| {
+            let frame_id = (seconds * fps).round() as i32;
+            let offset = (seconds - frame_id as f64 / fps).abs();
+            let entry = Entry {
+                agent_id,
+                frame_id,
+                position,
+                time: Some(seconds as f32),
+                group,
+            };
+            (offset, entry)
+        })
+        .collect();
+    entries.sort_by(|(a_offset, a), (b_offset, b)| {
+        (a.frame_id, a.agent_id)
+            .cmp(&(b.frame_id, b.agent_id))
+            .then(
+                a_offset
+                    .partial_cmp(b_offset)
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            )
+    });
+    entries.dedup_by_key(|(_, entry)| (entry.frame_id, entry.agent_id));
+    let mut builder = TrajectoryBuilder::new().fps(fps as f32);
+    for (_, entry) in entries {
+        builder.push(entry);
+    }
+    progress(1.0);
+    builder.build()
+}
+
+#[cfg(feature = "sqlite")]
+fn open_jupedsim_database(path: &Path) -> Result<rusqlite::Connection, ParseError> {
+    let connection =
+        rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let has_table = |name: &str| {
+        connection.query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [name],
+            |row| row.get::<_, i64>(0),
+        )
+    };
+    if has_table("trajectory_data")? == 0 {
+        return Err(ParseError::Unsupported(format!(
+            "'{}' is not a JuPedSim trajectory, it has no trajectory_data table",
+            path.display()
+        )));
+    }
+    Ok(connection)
+}
+
+// Reads the sqlite database written by JuPedSim, positions come from the
+// `trajectory_data` table and the frame rate from the `fps` entry of the
+// `metadata` table. If the database stores its geometry as well, the
+// trajectory references the database itself as its geometry file.
+#[cfg(feature = "sqlite")]
+pub fn parse_trajectory_sqlite(path: &Path) -> Result<Trajectory, ParseError> {
+    use rusqlite::OptionalExtension;
+
+    let connection = open_jupedsim_database(path)?;
+    let fps = connection
+        .query_row("SELECT value FROM metadata WHERE key = 'fps'", [], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()?
+        .and_then(|fps| fps.trim().parse::<f32>().ok())
+        .filter(|fps| *fps > 0.0);
+    let mut statement =
+        connection.prepare("SELECT frame, id, pos_x, pos_y FROM trajectory_data")?;
+    let mut builder = TrajectoryBuilder::new();
+    let entries = statement.query_map([], |row| {
+        Ok(Entry {
+            frame_id: row.get(0)?,
+            agent_id: row.get(1)?,
+            position: [row.get::<_, f64>(2)? as f32, row.get::<_, f64>(3)? as f32],
+            time: None,
+            group: None,
+        })
+    })?;
+    for entry in entries {
+        builder.push(entry?);
+    }
+    if let Some(fps) = fps {
+        builder = builder.fps(fps);
+    }
+    let mut trajectory = builder.build()?;
+    let has_geometry = connection
+        .query_row("SELECT 1 FROM geometry LIMIT 1", [], |_| Ok(()))
+        .optional()
+        .is_ok_and(|row| row.is_some());
+    if has_geometry {
+        trajectory.geometry = path.file_name().map(PathBuf::from);
+    }
+    Ok(trajectory)
+}
+
+// JuPedSim stores one WKT geometry per change of the walkable area, the
+// first one is used.
+#[cfg(feature = "sqlite")]
+pub fn parse_geometry_sqlite(path: &Path) -> Result<Geometry, ParseError> {
+    use rusqlite::OptionalExtension;
+
+    let connection = open_jupedsim_database(path)?;
+    let wkt = connection
+        .query_row("SELECT wkt FROM geometry LIMIT 1", [], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()?
+        .ok_or_else(|| {
+            ParseError::Unsupported(format!("'{}' contains no geometry", path.display()))
+        })?;
+    geometry::parse_wkt(&wkt)
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn parse_trajectory_sqlite(_path: &Path) -> Result<Trajectory, ParseError> {
+    Err(ParseError::Unsupported(
+        "sqlite trajectories require building with the 'sqlite' feature".to_string(),
+    ))
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn parse_geometry_sqlite(_path: &Path) -> Result<Geometry, ParseError> {
+    Err(ParseError::Unsupported(
+        "sqlite geometry requires building with the 'sqlite' feature".to_string(),
+    ))
+}
+
+// Reads an HDF5 file with the one dimensional datasets `frame`, `id`, `x`
+// and `y` of equal length, one row per agent and frame, and an optional
+// `fps` attribute on the root group. The rows have to be ordered by frame.
+// The datasets are read a chunk of rows at a time and every frame is grouped
+// once the next one starts, so besides the frames only one chunk is kept in
+// memory.
+#[cfg(feature = "hdf5")]
+pub fn parse_trajectory_hdf5(
+    path: &Path,
+    progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    use std::convert::TryFrom;
+
+    const CHUNK_ROWS: usize = 1 << 20;
+
+    let file = hdf5::File::open(path)?;
+    let dataset = |name: &str| {
+        file.dataset(name).map_err(|e| {
+            ParseError::Unsupported(format!(
+                "'{}' has no '{}' dataset: {}",
+                path.display(),
+                name,
+                e
+            ))
+        })
+    };
+    let frame_ids = dataset("frame")?;
+    let agent_ids = dataset("id")?;
+    let xs = dataset("x")?;
+    let ys = dataset("y")?;
+    let rows = frame_ids.size();
+    if [&agent_ids, &xs, &ys].iter().any(|d| d.size() != rows) {
+        return Err(ParseError::Invalid(
+            "the frame, id, x and y datasets differ in length".to_string(),
+        ));
+    }
+    let fps = file
+        .attr("fps")
+        .and_then(|attr| attr.read_scalar::<f64>())
+        .ok()
+        .map(|fps| fps as f32)
+        .filter(|fps| *fps > 0.0);
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut last_frame_id = None;
+    for start in (0..rows).step_by(CHUNK_ROWS) {
+        let end = (start + CHUNK_ROWS).min(rows);
+        let frame_chunk = frame_ids.read_slice_1d::<i64, _>(start..end)?;
+        let agent_chunk = agent_ids.read_slice_1d::<i64, _>(start..end)?;
+        let x_chunk = xs.read_slice_1d::<f64, _>(start..end)?;
+        let y_chunk = ys.read_slice_1d::<f64, _>(start..end)?;
+        let chunk_rows = frame_chunk
+            .iter()
+            .zip(&agent_chunk)
+            .zip(x_chunk.iter().zip(&y_chunk));
+        for (offset, ((frame_id, agent_id), (x, y))) in chunk_rows.enumerate() {
+            let row = start + offset;
+            let invalid =
+                |message: String| ParseError::Invalid(format!("row {}: {}", row, message));
+            let frame_id = i32::try_from(*frame_id)
+                .map_err(|_| invalid(format!("invalid frame {}", frame_id)))?;
+            let agent_id = u32::try_from(*agent_id)
+                .map_err(|_| invalid(format!("invalid agent id {}", agent_id)))?;
+            match last_frame_id {
+                Some(last) if frame_id < last => {
+                    return Err(invalid(format!(
+                        "frame {} follows frame {}, the rows are not ordered by frame",
+                        frame_id, last
+                    )));
+                }
+                Some(last) if frame_id > last => {
+                    push_frame(&mut frames, std::mem::take(&mut entries))?;
+                }
+                _ => {}
+            }
+            last_frame_id = Some(frame_id);
+            entries.push(Entry {
+                frame_id,
+                agent_id,
+                position: [*x as f32, *y as f32],
+                time: None,
+                group: None,
+            });
+        }
+        progress(end as f64 / rows as f64);
+    }
+    push_frame(&mut frames, entries)?;
+    if frames.is_empty() {
+        return Err(ParseError::NoFrames);
+    }
+    Ok(Trajectory::new(frames, fps))
+}
+
+// Groups the entries of a single frame and appends it to `frames`.
+#[cfg(feature = "hdf5")]
+fn push_frame(frames: &mut Vec<Frame>, entries: Vec<Entry>) -> Result<(), ParseError> {
+    if let Some(frame) = legacy_parsers::frames_from_entries(entries).pop() {
+        builder::validate_frame(&frame, frames.len())?;
+        frames.push(frame);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "hdf5"))]
+pub fn parse_trajectory_hdf5(
+    _path: &Path,
+    _progress: &mut dyn FnMut(f64),
+) -> Result<Trajectory, ParseError> {
+    Err(ParseError::Unsupported(
+        "HDF5 trajectories require building with the 'hdf5' feature".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn temp_file(suffix: &str, content: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn json_frames_are_sorted_by_id() {
+        let file = temp_file(
+            ".json",
+            r#"{"fps": 10, "frames": [
+                {"ids": [3, 1, 2], "positions": [[3, 0], [1, 0], [2, 0]], "groups": [30, 10, 20]}
+            ]}"#,
+        );
+        let trajectory = parse_trajectory_json(file.path()).unwrap();
+        let frame = &trajectory.frames[0];
+        assert_eq!(frame.ids, vec![1, 2, 3]);
+        assert_eq!(frame.positions, vec![[1.0, 0.0], [2.0, 0.0], [3.0, 0.0]]);
+        assert_eq!(frame.groups, vec![10, 20, 30]);
+        assert_eq!(frame.position_of(3), Some([3.0, 0.0]));
+    }
+
+    #[test]
+    fn json_rejects_duplicate_ids() {
+        let file = temp_file(
+            ".json",
+            r#"{"fps": 10, "frames": [
+                {"ids": [2, 1, 2], "positions": [[0, 0], [1, 0], [2, 0]]}
+            ]}"#,
+        );
+        assert!(matches!(
+            parse_trajectory_json(file.path()),
+            Err(ParseError::Invalid(_))
+        ));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn jupedsim_database_with_geometry() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("run.sqlite");
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE trajectory_data (frame INTEGER, id INTEGER, pos_x REAL, pos_y REAL);
+                 CREATE TABLE metadata (key TEXT, value TEXT);
+                 CREATE TABLE geometry (wkt TEXT);
+                 INSERT INTO trajectory_data VALUES (0, 1, 0.5, 1.0), (1, 1, 1.0, 1.0), (0, 2, 2.0, 2.0);
+                 INSERT INTO metadata VALUES ('fps', '8');
+                 INSERT INTO geometry VALUES ('POLYGON ((0 0, 4 0, 4 4, 0 4, 0 0))');",
+            )
+            .unwrap();
+        drop(connection);
+        let trajectory = parse_trajectory(&path, &mut |_| {}).unwrap();
+        assert_eq!(trajectory.fps, Some(8.0));
+        assert_eq!(trajectory.frames[0].ids, vec![1, 2]);
+        assert_eq!(trajectory.frames[1].position_of(1), Some([1.0, 1.0]));
+        assert_eq!(trajectory.geometry, Some(PathBuf::from("run.sqlite")));
+        let geometry = parse_geometry(&path).unwrap();
+        assert_eq!(geometry.area(), (0.0, 4.0, 0.0, 4.0));
+    }
+
+    #[cfg(feature = "hdf5")]
+    #[test]
+    fn hdf5_datasets_are_read_by_name() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("run.h5");
+        let file = hdf5::File::create(&path).unwrap();
+        let write = |name: &str, data: &[f64]| {
+            file.new_dataset_builder()
+                .with_data(data)
+                .create(name)
+                .unwrap();
+        };
+        write("frame", &[0.0, 0.0, 1.0]);
+        write("id", &[1.0, 2.0, 1.0]);
+        write("x", &[0.5, 2.0, 1.0]);
+        write("y", &[1.0, 2.0, 1.0]);
+        file.new_attr::<f64>()
+            .create("fps")
+            .unwrap()
+            .write_scalar(&8.0)
+            .unwrap();
+        drop(file);
+        let trajectory = parse_trajectory(&path, &mut |_| {}).unwrap();
+        assert_eq!(trajectory.fps, Some(8.0));
+        assert_eq!(trajectory.frames[0].ids, vec![1, 2]);
+        assert_eq!(trajectory.frames[1].position_of(1), Some([1.0, 1.0]));
+    }
+
+    #[cfg(feature = "hdf5")]
+    #[test]
+    fn hdf5_rejects_negative_ids_and_unordered_rows() {
+        let directory = tempfile::tempdir().unwrap();
+        let cases: [(&str, [f64; 2], [f64; 2]); 2] = [
+            ("negative.h5", [0.0, 0.0], [1.0, -1.0]),
+            ("unordered.h5", [1.0, 0.0], [1.0, 2.0]),
+        ];
+        for (name, frames, ids) in cases.iter() {
+            let path = directory.path().join(name);
+            let file = hdf5::File::create(&path).unwrap();
+            let write = |name: &str, data: &[f64]| {
+                file.new_dataset_builder()
+                    .with_data(data)
+                    .create(name)
+                    .unwrap();
+            };
+            write("frame", frames);
+            write("id", ids);
+            write("x", &[0.0, 1.0]);
+            write("y", &[0.0, 1.0]);
+            drop(file);
+            assert!(parse_trajectory(&path, &mut |_| {}).is_err());
+        }
+    }
+
+    #[test]
+    fn csv_columns_are_mapped_by_name() {
+        let file = temp_file(
+            ".csv",
+            "x,frame,y,id\n1.0,0,2.0,7\n3.0,1,4.0,7\n0.5,0,0.5,3\n",
+        );
+        let trajectory = parse_trajectory(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(trajectory.frames.len(), 2);
+        assert_eq!(trajectory.frames[0].ids, vec![3, 7]);
+        assert_eq!(trajectory.frames[0].positions, vec![[0.5, 0.5], [1.0, 2.0]]);
+        assert_eq!(trajectory.frames[1].position_of(7), Some([3.0, 4.0]));
+    }
+
+    #[test]
+    fn csv_time_column_is_optional() {
+        let file = temp_file(
+            ".csv",
+            "id,frame,x,y,time\n1,0,0.0,0.0,0.0\n1,1,1.0,0.0,0.25\n1,2,2.0,0.0,1.0\n",
+        );
+        let trajectory = parse_trajectory(file.path(), &mut |_| {}).unwrap();
+        let times: Vec<Option<f32>> = trajectory.frames.iter().map(|f| f.time).collect();
+        assert_eq!(times, vec![Some(0.0), Some(0.25), Some(1.0)]);
+        let file = temp_file(".csv", "id,frame,x,y\n1,0,0.0,0.0\n");
+        let trajectory = parse_trajectory(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(trajectory.frames[0].time, None);
+    }
+
+    #[test]
+    fn csv_group_column_is_optional() {
+        let file = temp_file(
+            ".csv",
+            "id,frame,x,y,group\n2,0,0.0,0.0,1\n1,0,1.0,0.0,3\n1,1,1.0,1.0,3\n",
+        );
+        let trajectory = parse_trajectory(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(trajectory.frames[0].ids, vec![1, 2]);
+        assert_eq!(trajectory.frames[0].groups, vec![3, 1]);
+        assert_eq!(trajectory.groups(), vec![1, 3]);
+        let file = temp_file(".csv", "id,frame,x,y\n1,0,0.0,0.0\n");
+        let trajectory = parse_trajectory(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(trajectory.frames[0].group(0), 0);
+        assert!(trajectory.groups().is_empty());
+    }
+
+    #[test]
+    fn csv_delimiter_is_detected() {
+        let file = temp_file(".csv", "id;frame;x;y\n1;0;0.5;1.5\n");
+        assert_eq!(sniff_csv_delimiter(file.path()).unwrap(), b';');
+        let trajectory = parse_trajectory(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(trajectory.frames[0].positions, vec![[0.5, 1.5]]);
+    }
+
+    #[test]
+    fn csv_columns_can_be_chosen() {
+        let file = temp_file(".csv", "0\t0.5\t1.5\t7\n1\t1.0\t1.5\t7\n");
+        let delimiter = sniff_csv_delimiter(file.path()).unwrap();
+        let row = csv_first_row(file.path(), delimiter).unwrap();
+        let mut layout = CsvLayout::guess(&row, delimiter);
+        assert_eq!(layout.delimiter, b'\t');
+        assert!(!layout.has_headers);
+        layout.frame = 0;
+        layout.x = 1;
+        layout.y = 2;
+        layout.id = 3;
+        let trajectory = parse_trajectory_csv_with(file.path(), &layout, &mut |_| {}).unwrap();
+        assert_eq!(trajectory.frames.len(), 2);
+        assert_eq!(trajectory.frames[1].position_of(7), Some([1.0, 1.5]));
+    }
+
+    #[test]
+    fn vadere_time_steps() {
+        let file = temp_file(
+            ".traj",
+            "timeStep pedestrianId x y targetId\n1 1 0.5 1.0 1\n1 2 2.0 2.0 1\n2 1 1.0 1.0 1\n",
+        );
+        let trajectory = parse_trajectory(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(trajectory.fps, Some(2.5));
+        assert_eq!(trajectory.frames[0].ids, vec![1, 2]);
+        assert_eq!(trajectory.frames[1].position_of(1), Some([1.0, 1.0]));
+    }
+
+    #[test]
+    fn vadere_footsteps_are_sampled() {
+        let file = temp_file(
+            ".traj",
+            "#IDXCOL=2,DATACOL=5,SEP=' '\n\
+             pedestrianId simTime endTime-PID1 startX-PID1 startY-PID1 endX-PID1 endY-PID1\n\
+             1 0.0 0.2 0.0 0.0 2.0 0.0\n\
+             1 0.2 0.3 2.0 0.0 2.0 1.0\n",
+        );
+        let trajectory = parse_trajectory(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(trajectory.fps, Some(10.0));
+        let positions: Vec<[f32; 2]> = trajectory.frames.iter().map(|f| f.positions[0]).collect();
+        assert_eq!(positions, vec![[0.0, 0.0], [1.0, 0.0], [2.0, 0.0]]);
+    }
+
+    #[test]
+    fn fzp_records_are_mapped_to_frames() {
+        let content = "$VISION\n\
+                       * Table: Pedestrians\n\
+                       *\n\
+                       $PEDESTRIAN:SIMSEC;NO;PEDTYPE;COORDCENTX;COORDCENTY\n\
+                       0.50;1;100;1.0;2.0\n\
+                       0.50;2;200;3.0;4.0\n\
+                       1.00;1;100;1.5;2.0\n";
+        let file = temp_file(".fzp", content);
+        let trajectory = parse_trajectory(file.path(), &mut |_| {}).unwrap();
+        assert_eq!(trajectory.fps, Some(2.0));
+        assert_eq!(trajectory.frames.len(), 2);
+        assert_eq!(trajectory.frames[0].ids, vec![1, 2]);
+        assert_eq!(trajectory.frames[0].groups, vec![100, 200]);
+        assert_eq!(trajectory.frames[1].position_of(1), Some([1.5, 2.0]));
+        let times: Vec<Option<f32>> = trajectory.frames.iter().map(|f| f.time).collect();
+        assert_eq!(times, vec![Some(0.5), Some(1.0)]);
+        // Frames in between have no records, the timestamps keep playback
+        // at the pace of the simulation.
+        let trajectory = parse_trajectory_fzp(file.path(), Some(10.0), &mut |_| {}).unwrap();
+        assert_eq!(trajectory.fps, Some(10.0));
+        let times: Vec<Option<f32>> = trajectory.frames.iter().map(|f| f.time).collect();
+        assert_eq!(times, vec![Some(0.5), Some(1.0)]);
+    }
+
+    #[test]
+    fn fzp_rate_below_record_interval_keeps_nearest_record() {
+        let content = "$PEDESTRIAN:SIMSEC;NO;COORDCENTX;COORDCENTY\n\
+                       0.0;1;0.0;0.0\n\
+                       0.1;1;1.0;0.0\n\
+                       0.2;1;2.0;0.0\n\
+                       0.3;1;3.0;0.0\n\
+                       0.4;1;4.0;0.0\n";
+        let file = temp_file(".fzp", content);
+        let trajectory = parse_trajectory_fzp(file.path(), Some(5.0), &mut |_| {}).unwrap();
+        let positions: Vec<[f32; 2]> = trajectory.frames.iter().map(|f| f.positions[0]).collect();
+        assert_eq!(positions, vec![[0.0, 0.0], [2.0, 0.0], [4.0, 0.0]]);
+        let times: Vec<Option<f32>> = trajectory.frames.iter().map(|f| f.time).collect();
+        assert_eq!(times, vec![Some(0.0), Some(0.2), Some(0.4)]);
+    }
+
+    #[test]
+    fn fzp_missing_column_is_reported_at_the_header() {
+        let content = "* Table: Pedestrians\n\
+                       $PEDESTRIAN:SIMSEC;NO;COORDCENTX\n\
+                       0.0;1;0.0\n";
+        let file = temp_file(".fzp", content);
+        let result = parse_trajectory_fzp(file.path(), None, &mut |_| {});
+        assert!(matches!(result, Err(ParseError::Syntax { line: 2, .. })));
+    }
+
+    #[test]
+    fn folders_list_trajectory_files_by_name() {
+        let directory = tempfile::tempdir().unwrap();
+        for name in &["b.txt", "a.CSV", "notes.md", "geometry.wkt"] {
+            std::fs::write(directory.path().join(name), "").unwrap();
+        }
+        std::fs::create_dir(directory.path().join("c.txt")).unwrap();
+        let files = trajectory_files_in(directory.path()).unwrap();
+        let names: Vec<_> = files.iter().map(|f| f.file_name().unwrap()).collect();
+        assert_eq!(names, vec!["a.CSV", "b.txt"]);
+    }
+
+    #[test]
+    fn files_without_frames_are_rejected() {
+        let file = temp_file(".txt", "# framerate: 16.00\n# id\tframe\tx\ty\n");
+        let result = parse_trajectory(file.path(), &mut |_| {});
+        let streamed = parse_trajectory_frame_ordered(file.path(), &mut |_| {});
+        assert!(matches!(result, Err(ParseError::NoFrames)));
+        assert!(matches!(streamed, Err(ParseError::NoFrames)));
+    }
+}
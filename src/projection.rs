@@ -0,0 +1,102 @@
+// Transforms between world coordinates, the clip space the shaders render
+// to and window pixels. Bounds are `(left, right, bottom, top)` in world
+// units, screen positions are pixels with the origin in the top left corner
+// of a viewport of `[width, height]`.
+
+pub type Bounds = (f32, f32, f32, f32);
+
+// Orthographic projection of `bounds` onto clip space, column-major as
+// expected by the `projection` uniform of the shaders.
+pub fn ortho(bounds: Bounds) -> [[f32; 4]; 4] {
+    let (left, right, bottom, top) = bounds;
+    [
+        [2.0 / (right - left), 0.0, 0.0, 0.0],
+        [0.0, 2.0 / (top - bottom), 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [
+            -(right + left) / (right - left),
+            -(top + bottom) / (top - bottom),
+            0.0,
+            1.0,
+        ],
+    ]
+}
+
+pub fn world_to_screen(world: [f32; 2], bounds: Bounds, viewport: [f32; 2]) -> [f32; 2] {
+    let (left, right, bottom, top) = bounds;
+    [
+        (world[0] - left) / (right - left) * viewport[0],
+        (top - world[1]) / (top - bottom) * viewport[1],
+    ]
+}
+
+pub fn screen_to_world(screen: [f32; 2], bounds: Bounds, viewport: [f32; 2]) -> [f32; 2] {
+    let (left, right, bottom, top) = bounds;
+    [
+        left + screen[0] / viewport[0] * (right - left),
+        top - screen[1] / viewport[1] * (top - bottom),
+    ]
+}
+
+// Widens `bounds` along one axis so they match `display_aspect`, which makes
+// one world unit cover the same number of pixels in both directions.
+pub fn fit_aspect_ratio(bounds: Bounds, display_aspect: f32) -> Bounds {
+    let (left, right, bottom, top) = bounds;
+    let width = right - left;
+    let height = top - bottom;
+    if width / height > display_aspect {
+        let delta = (width / display_aspect - height) / 2.0;
+        (left, right, bottom - delta, top + delta)
+    } else {
+        let delta = (height * display_aspect - width) / 2.0;
+        (left - delta, right + delta, bottom, top)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: Bounds = (-3.0, 5.0, 1.0, 7.0);
+    const VIEWPORT: [f32; 2] = [800.0, 600.0];
+
+    fn assert_close(actual: [f32; 2], expected: [f32; 2]) {
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!((a - e).abs() < 1e-3, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn screen_to_world_inverts_world_to_screen() {
+        for p in [[0.0, 0.0], [-3.0, 7.0], [5.0, 1.0], [12.5, -4.25]].iter() {
+            let screen = world_to_screen(*p, BOUNDS, VIEWPORT);
+            assert_close(screen_to_world(screen, BOUNDS, VIEWPORT), *p);
+        }
+        assert_close(world_to_screen([-3.0, 7.0], BOUNDS, VIEWPORT), [0.0, 0.0]);
+        assert_close(world_to_screen([5.0, 1.0], BOUNDS, VIEWPORT), VIEWPORT);
+    }
+
+    #[test]
+    fn ortho_matches_world_to_screen() {
+        let m = ortho(BOUNDS);
+        for p in [[0.0, 0.0], [-3.0, 7.0], [2.0, 3.5]].iter() {
+            let clip_x = m[0][0] * p[0] + m[1][0] * p[1] + m[3][0];
+            let clip_y = m[0][1] * p[0] + m[1][1] * p[1] + m[3][1];
+            let screen = [
+                (clip_x + 1.0) / 2.0 * VIEWPORT[0],
+                (1.0 - clip_y) / 2.0 * VIEWPORT[1],
+            ];
+            assert_close(screen, world_to_screen(*p, BOUNDS, VIEWPORT));
+        }
+    }
+
+    #[test]
+    fn aspect_ratio_is_fixed_by_widening() {
+        let (left, right, bottom, top) = fit_aspect_ratio((0.0, 4.0, 0.0, 1.0), 2.0);
+        assert_eq!((left, right), (0.0, 4.0));
+        assert_eq!((bottom, top), (-0.5, 1.5));
+        let (left, right, bottom, top) = fit_aspect_ratio((0.0, 1.0, 0.0, 1.0), 2.0);
+        assert_eq!((left, right), (-0.5, 1.5));
+        assert_eq!((bottom, top), (0.0, 1.0));
+    }
+}
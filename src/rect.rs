@@ -0,0 +1,55 @@
+// Axis aligned rectangle in world coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Rect {
+    // Rectangle spanned by two opposite corners in any order, `None` if it
+    // has no area.
+    pub fn from_corners(a: [f32; 2], b: [f32; 2]) -> Option<Self> {
+        let rect = Self {
+            min: [a[0].min(b[0]), a[1].min(b[1])],
+            max: [a[0].max(b[0]), a[1].max(b[1])],
+        };
+        if rect.min[0] < rect.max[0] && rect.min[1] < rect.max[1] {
+            Some(rect)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains(&self, p: [f32; 2]) -> bool {
+        p[0] >= self.min[0] && p[0] <= self.max[0] && p[1] >= self.min[1] && p[1] <= self.max[1]
+    }
+
+    // The four edges, in the segment format used for trails.
+    pub fn outline(&self) -> Vec<([f32; 2], [f32; 2], f32)> {
+        let corners = [
+            self.min,
+            [self.max[0], self.min[1]],
+            self.max,
+            [self.min[0], self.max[1]],
+        ];
+        (0..4)
+            .map(|i| (corners[i], corners[(i + 1) % 4], 1.0))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corners_are_normalized() {
+        let rect = Rect::from_corners([2.0, -1.0], [0.0, 3.0]).unwrap();
+        assert_eq!(rect.min, [0.0, -1.0]);
+        assert_eq!(rect.max, [2.0, 3.0]);
+        assert!(rect.contains([1.0, 0.0]));
+        assert!(rect.contains([2.0, 3.0]));
+        assert!(!rect.contains([2.5, 0.0]));
+        assert_eq!(Rect::from_corners([1.0, 1.0], [1.0, 4.0]), None);
+    }
+}
@@ -0,0 +1,997 @@
+use glium::backend::{Context, Facade};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::NoIndices;
+use glium::index::PrimitiveType;
+use glium::texture::{Texture1d, Texture2d, Texture2dMultisample};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction};
+use glium::{BlitTarget, Program, Surface, VertexBuffer};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::colormap;
+use crate::colormap::Colormap;
+use crate::diff;
+use crate::diff::Displacement;
+use crate::error::InitError;
+use crate::geometry::Geometry;
+use crate::heatmap::Heatmap;
+use crate::layers;
+use crate::layers::LoadedTrajectory;
+use crate::projection;
+use crate::rect::Rect;
+use crate::settings::Settings;
+use crate::ApplicationState;
+
+#[derive(Clone, Copy, Debug)]
+struct Vertex {
+    position: [f32; 3],
+}
+glium::implement_vertex!(Vertex, position);
+
+#[derive(Clone, Copy, Debug)]
+struct VertexInstanceAttributes {
+    offset: [f32; 2],
+    speed: f32,
+    selected: f32,
+    dimmed: f32,
+    heading: f32,
+    tint: [f32; 3],
+}
+glium::implement_vertex!(
+    VertexInstanceAttributes,
+    offset,
+    speed,
+    selected,
+    dimmed,
+    heading,
+    tint
+);
+
+#[derive(Clone, Copy, Debug)]
+struct TrailVertex {
+    position: [f32; 2],
+    alpha: f32,
+}
+glium::implement_vertex!(TrailVertex, position, alpha);
+
+#[derive(Clone, Copy, Debug)]
+struct ColoredVertex {
+    position: [f32; 2],
+    color: [f32; 3],
+}
+glium::implement_vertex!(ColoredVertex, position, color);
+
+#[derive(Clone, Copy, Debug)]
+struct HeatmapCell {
+    center: [f32; 2],
+    density: f32,
+}
+glium::implement_vertex!(HeatmapCell, center, density);
+
+#[derive(Clone, Copy, Debug)]
+struct GeometryVertex {
+    position: [f32; 2],
+}
+glium::implement_vertex!(GeometryVertex, position);
+
+// How one pass over the agents of a frame is colored. Solid passes ignore
+// the speed and use `color` as is, otherwise it tints the speed colormap.
+#[derive(Clone, Copy, Debug)]
+pub struct AgentPass {
+    pub color: [f32; 3],
+    pub alpha: f32,
+    pub solid: bool,
+    // Uses the color of each agent's group instead of `color`.
+    pub group_colors: bool,
+    // Draws a marker pointing in the direction of motion in front of each
+    // moving agent instead of the agent itself.
+    pub headings: bool,
+    pub selected_id: Option<u32>,
+    // Agents outside are dimmed, or skipped if `hide_outside_roi` is set.
+    pub roi: Option<Rect>,
+}
+
+// Upper bound for grid lines along each axis, the spacing is coarsened by
+// the major line interval until the visible lines fit.
+const MAX_GRID_LINES: f32 = 200.0;
+const GRID_MAJOR_INTERVAL: i64 = 5;
+const GRID_MINOR_ALPHA: f32 = 0.1;
+const GRID_MAJOR_ALPHA: f32 = 0.3;
+
+// Onion-skin frames before the current one are drawn in `ONION_PAST_COLOR`,
+// the ones after it in `ONION_FUTURE_COLOR`. The closest neighbors start at
+// `ONION_ALPHA`, every further frame is multiplied by the falloff.
+const ONION_PAST_COLOR: [f32; 3] = [0.3, 0.5, 1.0];
+const ONION_FUTURE_COLOR: [f32; 3] = [1.0, 0.5, 0.2];
+const ONION_ALPHA: f32 = 0.5;
+const HEADING_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+const WALL_COLOR: [f32; 3] = [0.9, 0.9, 0.9];
+const OBSTACLE_COLOR: [f32; 3] = [0.9, 0.6, 0.3];
+const DOOR_COLOR: [f32; 3] = [0.3, 0.7, 0.9];
+
+// Number of samples in the lookup textures the shaders read colormaps from.
+const COLORMAP_TEXELS: usize = 256;
+
+const AGENT_VERTEX_SHADER: &str = r#"
+    #version 140
+
+    in vec3 position;
+    in vec2 offset;
+    in float speed;
+    in float selected;
+    in float dimmed;
+    in float heading;
+    in vec3 tint;
+    uniform mat4 projection;
+    uniform float radius;
+    uniform float max_speed;
+    uniform bool marker;
+
+    out float vertex_speed;
+    out float vertex_selected;
+    out float vertex_dimmed;
+    out vec3 vertex_tint;
+
+    mat4 scale(float x, float y, float z) {
+        return mat4(
+            x, 0, 0, 0,
+            0, y, 0, 0,
+            0, 0, z, 0,
+            0, 0, 0, 1
+        );
+    }
+
+    mat4 trans(vec3 t) {
+        return mat4(
+              1,   0,   0,   0,
+              0,   1,   0,   0,
+              0,   0,   1,   0,
+            t.x, t.y, t.z,   1
+        );
+    }
+
+    mat4 rotZ(float rad) {
+        float sin_rad = sin(rad);
+        float cos_rad = cos(rad);
+        return mat4(
+            cos_rad, -sin_rad, 0.0, 0.0,
+            sin_rad,  cos_rad, 0.0, 0.0,
+                0.0,      0.0, 1.0, 0.0,
+                0.0,      0.0, 0.0, 1.0
+        );
+    }
+
+    void main() {
+        vec4 p = vec4(position, 1.0);
+        if (marker) {
+            // The marker grows from the edge of the agent with its speed.
+            float extent = 0.5 + clamp(speed / max_speed, 0.0, 1.0);
+            p.x = 1.0 + (p.x - 1.0) * extent;
+            // rotZ turns clockwise.
+            p = rotZ(-heading) * p;
+        }
+        gl_Position = projection * trans(vec3(offset, 0.0)) * scale(radius, radius, radius) * p;
+        vertex_speed = speed;
+        vertex_selected = selected;
+        vertex_dimmed = dimmed;
+        vertex_tint = tint;
+    }
+"#;
+
+const AGENT_FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    in float vertex_speed;
+    in float vertex_selected;
+    in float vertex_dimmed;
+    in vec3 vertex_tint;
+    uniform float max_speed;
+    uniform float teleport_speed;
+    uniform sampler1D colormap;
+    uniform float alpha;
+    uniform bool solid;
+    out vec4 frag_color;
+
+    vec3 speed_colormap(float speed) {
+        if (speed < 0.0) {
+            return vec3(0.5, 0.5, 0.5);
+        }
+        float t = clamp(speed / max_speed, 0.0, 1.0);
+        float texels = float(textureSize(colormap, 0));
+        return texture(colormap, (t * (texels - 1.0) + 0.5) / texels).rgb;
+    }
+
+    void main() {
+        if (vertex_selected > 0.5) {
+            frag_color = vec4(1.0, 1.0, 0.0, 1.0);
+            return;
+        }
+        if (vertex_speed > teleport_speed) {
+            frag_color = vec4(1.0, 0.0, 0.0, alpha);
+            return;
+        }
+        if (vertex_dimmed > 0.5) {
+            frag_color = vec4(0.5, 0.5, 0.5, 0.25 * alpha);
+            return;
+        }
+        if (solid) {
+            frag_color = vec4(vertex_tint, alpha);
+            return;
+        }
+        frag_color = vec4(speed_colormap(vertex_speed) * vertex_tint, alpha);
+    }
+"#;
+
+const TRAIL_VERTEX_SHADER: &str = r#"
+    #version 140
+
+    in vec2 position;
+    in float alpha;
+    uniform mat4 projection;
+
+    out float vertex_alpha;
+
+    void main() {
+        gl_Position = projection * vec4(position, 0.0, 1.0);
+        vertex_alpha = alpha;
+    }
+"#;
+
+const TRAIL_FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    in float vertex_alpha;
+    out vec4 frag_color;
+
+    void main() {
+        frag_color = vec4(0.8, 0.8, 0.8, vertex_alpha);
+    }
+"#;
+
+const COLORED_LINE_VERTEX_SHADER: &str = r#"
+    #version 140
+
+    in vec2 position;
+    in vec3 color;
+    uniform mat4 projection;
+
+    out vec3 vertex_color;
+
+    void main() {
+        gl_Position = projection * vec4(position, 0.0, 1.0);
+        vertex_color = color;
+    }
+"#;
+
+const COLORED_LINE_FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    in vec3 vertex_color;
+    out vec4 frag_color;
+
+    void main() {
+        frag_color = vec4(vertex_color, 1.0);
+    }
+"#;
+
+const HEATMAP_VERTEX_SHADER: &str = r#"
+    #version 140
+
+    in vec3 position;
+    in vec2 center;
+    in float density;
+    uniform mat4 projection;
+    uniform float cell_size;
+
+    out float vertex_density;
+
+    void main() {
+        vec2 world = center + position.xy * cell_size / 2.0;
+        gl_Position = projection * vec4(world, 0.0, 1.0);
+        vertex_density = density;
+    }
+"#;
+
+const HEATMAP_FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    in float vertex_density;
+    uniform sampler1D colormap;
+    out vec4 frag_color;
+
+    vec3 density_colormap(float t) {
+        float texels = float(textureSize(colormap, 0));
+        return texture(colormap, (t * (texels - 1.0) + 0.5) / texels).rgb;
+    }
+
+    void main() {
+        frag_color = vec4(density_colormap(clamp(vertex_density, 0.0, 1.0)), 0.6);
+    }
+"#;
+
+const GEOMETRY_VERTEX_SHADER: &str = r#"
+    #version 140
+
+    in vec2 position;
+    uniform mat4 projection;
+
+    void main() {
+        gl_Position = projection * vec4(position, 0.0, 1.0);
+    }
+"#;
+
+const GEOMETRY_FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    uniform vec3 line_color;
+    uniform float alpha;
+    out vec4 frag_color;
+
+    void main() {
+        frag_color = vec4(line_color, alpha);
+    }
+"#;
+
+// Triangle in front of a unit agent pointing along the x axis.
+fn make_marker() -> Vec<Vertex> {
+    vec![
+        Vertex {
+            position: [1.0, 0.4, 0.0],
+        },
+        Vertex {
+            position: [2.0, 0.0, 0.0],
+        },
+        Vertex {
+            position: [1.0, -0.4, 0.0],
+        },
+    ]
+}
+
+fn make_quad() -> Vec<Vertex> {
+    let extend = 1.0;
+    let top_left = [-extend, extend, 0.0];
+    let top_right = [extend, extend, 0.0];
+    let bottom_left = [-extend, -extend, 0.0];
+    let bottom_right = [extend, -extend, 0.0];
+    [
+        top_left,
+        top_right,
+        bottom_right,
+        top_left,
+        bottom_right,
+        bottom_left,
+    ]
+    .iter()
+    .map(|&position| Vertex { position })
+    .collect()
+}
+
+pub struct Renderer2D {
+    context: Rc<Context>,
+    agent_program: Program,
+    quad: VertexBuffer<Vertex>,
+    marker: VertexBuffer<Vertex>,
+    indices: NoIndices,
+    instances: Vec<VertexInstanceAttributes>,
+    instance_buffer: Option<VertexBuffer<VertexInstanceAttributes>>,
+    trail_program: Program,
+    colored_line_program: Program,
+    heatmap_program: Program,
+    geometry_program: Program,
+    geometry_buffer: Option<VertexBuffer<GeometryVertex>>,
+    obstacle_buffer: Option<VertexBuffer<GeometryVertex>>,
+    door_buffer: Option<VertexBuffer<GeometryVertex>>,
+    walkable_area_buffer: Option<VertexBuffer<GeometryVertex>>,
+    line_indices: NoIndices,
+    colormaps: HashMap<Colormap, Texture1d>,
+}
+
+impl Renderer2D {
+    // Works with both windows and headless contexts.
+    pub fn new<F: Facade>(facade: &F) -> Result<Self, InitError> {
+        let context = facade.get_context();
+        let agent_program =
+            Program::from_source(context, AGENT_VERTEX_SHADER, AGENT_FRAGMENT_SHADER, None)?;
+        let trail_program =
+            Program::from_source(context, TRAIL_VERTEX_SHADER, TRAIL_FRAGMENT_SHADER, None)?;
+        let colored_line_program = Program::from_source(
+            context,
+            COLORED_LINE_VERTEX_SHADER,
+            COLORED_LINE_FRAGMENT_SHADER,
+            None,
+        )?;
+        let heatmap_program = Program::from_source(
+            context,
+            HEATMAP_VERTEX_SHADER,
+            HEATMAP_FRAGMENT_SHADER,
+            None,
+        )?;
+        let geometry_program = Program::from_source(
+            context,
+            GEOMETRY_VERTEX_SHADER,
+            GEOMETRY_FRAGMENT_SHADER,
+            None,
+        )?;
+        Ok(Self {
+            context: context.clone(),
+            agent_program,
+            quad: VertexBuffer::new(context, &make_quad()).unwrap(),
+            marker: VertexBuffer::new(context, &make_marker()).unwrap(),
+            indices: NoIndices(PrimitiveType::TrianglesList),
+            instances: Vec::new(),
+            instance_buffer: None,
+            trail_program,
+            colored_line_program,
+            heatmap_program,
+            geometry_program,
+            geometry_buffer: None,
+            obstacle_buffer: None,
+            door_buffer: None,
+            walkable_area_buffer: None,
+            line_indices: NoIndices(PrimitiveType::LinesList),
+            colormaps: Colormap::ALL
+                .iter()
+                .map(|&map| {
+                    let texels = colormap::lookup_table(map, COLORMAP_TEXELS);
+                    (map, Texture1d::new(context, texels).unwrap())
+                })
+                .collect(),
+        })
+    }
+
+    fn colormap(&self, map: Colormap) -> Sampler<'_, Texture1d> {
+        self.colormaps[&map]
+            .sampled()
+            .wrap_function(SamplerWrapFunction::Clamp)
+            .minify_filter(MinifySamplerFilter::Linear)
+            .magnify_filter(MagnifySamplerFilter::Linear)
+    }
+
+    // Makes sure the instance buffer holds at least `count` agents. The
+    // buffer is only reallocated when it is too small.
+    pub fn reserve_instances(&mut self, count: usize) {
+        let capacity = self.instance_buffer.as_ref().map_or(0, |b| b.len());
+        if capacity < count {
+            self.instance_buffer = Some(VertexBuffer::empty_dynamic(&self.context, count).unwrap());
+        }
+    }
+
+    pub fn set_geometry(&mut self, geometry: Option<&Geometry>) {
+        let context = &self.context;
+        let buffer = |segments: Vec<[f32; 2]>| {
+            let vertices: Vec<GeometryVertex> = segments
+                .into_iter()
+                .map(|position| GeometryVertex { position })
+                .collect();
+            VertexBuffer::new(context, &vertices).unwrap()
+        };
+        self.geometry_buffer = geometry.map(|geometry| buffer(geometry.segments()));
+        self.obstacle_buffer = geometry.map(|geometry| buffer(geometry.hole_segments()));
+        self.door_buffer = geometry.map(|geometry| buffer(geometry.door_segments()));
+        self.walkable_area_buffer = geometry.map(|geometry| buffer(geometry.triangles()));
+    }
+
+    // Draws the grid, geometry, heatmap, trails and agents of `frame_index`
+    // as seen through `bounds`. Used for both the live view and exports.
+    // `frame_index` refers to the first layer, the other layers show the
+    // frame at the same point in time.
+    pub fn draw<S: Surface>(
+        &mut self,
+        target: &mut S,
+        state: &ApplicationState,
+        frame_index: usize,
+        heatmap: Option<&Heatmap>,
+        bounds: (f32, f32, f32, f32),
+    ) {
+        if state.settings.fill_walkable_area {
+            let color = state.settings.walkable_area_color;
+            let opacity = state.settings.walkable_area_opacity;
+            self.draw_walkable_area(target, bounds, color, opacity);
+        }
+        if state.settings.show_grid {
+            self.draw_grid(target, bounds, state.settings.grid_spacing);
+        }
+        self.draw_geometry(target, bounds);
+        if let Some(heatmap) = heatmap {
+            self.draw_heatmap(target, heatmap, bounds, state.settings.heatmap_colormap);
+        }
+        let settings = &state.settings;
+        let max_agent_count = state
+            .layers
+            .iter()
+            .map(|layer| layer.replay.max_agent_count())
+            .max()
+            .unwrap_or(0);
+        self.reserve_instances(max_agent_count);
+        if let Some((from, to)) = state.measurement.segment(state.camera.cursor_world()) {
+            self.draw_trail(target, &[(from, to, 1.0)], bounds);
+        }
+        let roi = state.visible_roi();
+        if let Some(roi) = roi {
+            self.draw_trail(target, &roi.outline(), bounds);
+        }
+        for (index, layer) in state.layers.iter().enumerate() {
+            if !layer.visible {
+                continue;
+            }
+            let replay = &layer.replay;
+            let frame_index = layers::frame_index(&state.layers, index, frame_index);
+            // Selection only applies to the first layer, ids of the other
+            // trajectories refer to different agents.
+            let selected_id = if index == 0 { state.selected_id } else { None };
+            if settings.show_trails {
+                let segments = replay.trail(frame_index, settings.trail_length as usize);
+                self.draw_trail(target, &segments, bounds);
+            }
+            if let Some(id) = selected_id {
+                let segments = replay.agent_trail(id, frame_index);
+                self.draw_trail(target, &segments, bounds);
+            }
+            let onion = onion_frames(
+                frame_index,
+                replay.frames(),
+                settings.onion_skin_frames as usize,
+                settings.onion_skin_falloff,
+            );
+            for (onion_index, alpha) in onion {
+                let color = if onion_index < frame_index {
+                    ONION_PAST_COLOR
+                } else {
+                    ONION_FUTURE_COLOR
+                };
+                let pass = AgentPass {
+                    color,
+                    alpha,
+                    solid: true,
+                    group_colors: false,
+                    headings: false,
+                    selected_id: None,
+                    roi,
+                };
+                self.draw_agents(target, layer, onion_index, bounds, settings, pass);
+            }
+            let group_colors = layer.color_by_group && !layer.groups.is_empty();
+            let pass = AgentPass {
+                color: layer.tint,
+                alpha: 1.0,
+                solid: !layer.color_by_speed || group_colors,
+                group_colors,
+                headings: false,
+                selected_id,
+                roi,
+            };
+            self.draw_agents(target, layer, frame_index, bounds, settings, pass);
+            if settings.show_headings {
+                let pass = AgentPass {
+                    color: HEADING_COLOR,
+                    solid: true,
+                    group_colors: false,
+                    headings: true,
+                    ..pass
+                };
+                self.draw_agents(target, layer, frame_index, bounds, settings, pass);
+            }
+        }
+        if let Some(pair) = state.diff_layers {
+            if let Some(displacements) = diff::layer_displacements(&state.layers, pair, frame_index)
+            {
+                self.draw_diff(target, &displacements, bounds, settings);
+            }
+        }
+    }
+
+    // Lines between the positions of the same agent in two layers, colored
+    // by their length. Differences below the threshold are left out.
+    pub fn draw_diff<S: Surface>(
+        &self,
+        target: &mut S,
+        displacements: &[Displacement],
+        bounds: (f32, f32, f32, f32),
+        settings: &Settings,
+    ) {
+        let mut vertices = Vec::new();
+        for displacement in displacements {
+            if displacement.distance < settings.diff_threshold {
+                continue;
+            }
+            let t = displacement.distance / settings.diff_scale.max(f32::EPSILON);
+            let color = colormap::sample(settings.speed_colormap, t.min(1.0));
+            for position in [displacement.from, displacement.to].iter() {
+                vertices.push(ColoredVertex {
+                    position: *position,
+                    color,
+                });
+            }
+        }
+        if vertices.is_empty() {
+            return;
+        }
+        let buffer = VertexBuffer::new(&self.context, &vertices).unwrap();
+        target
+            .draw(
+                &buffer,
+                self.line_indices,
+                &self.colored_line_program,
+                &glium::uniform! { projection: projection::ortho(bounds) },
+                &Default::default(),
+            )
+            .unwrap();
+    }
+
+    // Renders the scene like `draw` into a new texture of `size` pixels,
+    // which can be read back e.g. to save it. Uses the background and
+    // multisampling from the settings.
+    pub fn render_to_texture(
+        &mut self,
+        state: &ApplicationState,
+        frame_index: usize,
+        heatmap: Option<&Heatmap>,
+        bounds: (f32, f32, f32, f32),
+        size: (u32, u32),
+    ) -> Texture2d {
+        let (width, height) = size;
+        let context = self.context.clone();
+        let texture = Texture2d::empty(&context, width, height).unwrap();
+        let mut framebuffer = SimpleFrameBuffer::new(&context, &texture).unwrap();
+        let [r, g, b, a] = state.settings.background;
+        // Images are rendered into a multisampled texture first and resolved
+        // into `texture`, which can be read back.
+        let samples = u32::from(state.settings.multisampling);
+        let multisampled = if samples > 0 {
+            Texture2dMultisample::empty(&context, width, height, samples).ok()
+        } else {
+            None
+        };
+        match multisampled.as_ref() {
+            Some(multisampled) => {
+                let mut resolve = SimpleFrameBuffer::new(&context, multisampled).unwrap();
+                resolve.clear_color_srgb(r, g, b, a);
+                self.draw(&mut resolve, state, frame_index, heatmap, bounds);
+                resolve.blit_whole_color_to(
+                    &framebuffer,
+                    &BlitTarget {
+                        left: 0,
+                        bottom: 0,
+                        width: width as i32,
+                        height: height as i32,
+                    },
+                    MagnifySamplerFilter::Nearest,
+                );
+            }
+            None => {
+                framebuffer.clear_color_srgb(r, g, b, a);
+                self.draw(&mut framebuffer, state, frame_index, heatmap, bounds);
+            }
+        }
+        texture
+    }
+
+    pub fn draw_grid<S: Surface>(
+        &self,
+        target: &mut S,
+        bounds: (f32, f32, f32, f32),
+        spacing: f32,
+    ) {
+        let segments = grid_lines(bounds, spacing);
+        self.draw_trail(target, &segments, bounds);
+    }
+
+    // Walls, the outlines of obstacles and doors, in different colors.
+    pub fn draw_geometry<S: Surface>(&self, target: &mut S, bounds: (f32, f32, f32, f32)) {
+        let passes = [
+            (self.geometry_buffer.as_ref(), WALL_COLOR),
+            (self.obstacle_buffer.as_ref(), OBSTACLE_COLOR),
+            (self.door_buffer.as_ref(), DOOR_COLOR),
+        ];
+        for (buffer, color) in passes.iter() {
+            let buffer = match buffer {
+                Some(buffer) if buffer.len() > 0 => buffer,
+                _ => continue,
+            };
+            target
+                .draw(
+                    *buffer,
+                    self.line_indices,
+                    &self.geometry_program,
+                    &glium::uniform! {
+                        projection: projection::ortho(bounds),
+                        line_color: *color,
+                        alpha: 1.0f32,
+                    },
+                    &Default::default(),
+                )
+                .unwrap();
+        }
+    }
+
+    pub fn draw_walkable_area<S: Surface>(
+        &self,
+        target: &mut S,
+        bounds: (f32, f32, f32, f32),
+        color: [f32; 3],
+        opacity: f32,
+    ) {
+        let buffer = match self.walkable_area_buffer.as_ref() {
+            Some(buffer) if buffer.len() > 0 => buffer,
+            _ => return,
+        };
+        target
+            .draw(
+                buffer,
+                NoIndices(PrimitiveType::TrianglesList),
+                &self.geometry_program,
+                &glium::uniform! {
+                    projection: projection::ortho(bounds),
+                    line_color: color,
+                    alpha: opacity,
+                },
+                &glium::DrawParameters {
+                    blend: glium::Blend::alpha_blending(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+    }
+
+    pub fn draw_heatmap<S: Surface>(
+        &self,
+        target: &mut S,
+        heatmap: &Heatmap,
+        bounds: (f32, f32, f32, f32),
+        colormap: Colormap,
+    ) {
+        let max_count = heatmap.max_count();
+        if max_count == 0 {
+            return;
+        }
+        let cells: Vec<HeatmapCell> = heatmap
+            .cells()
+            .map(|(center, count)| HeatmapCell {
+                center,
+                density: count as f32 / max_count as f32,
+            })
+            .collect();
+        let buffer = VertexBuffer::new(&self.context, &cells).unwrap();
+        target
+            .draw(
+                (&self.quad, buffer.per_instance().unwrap()),
+                self.indices,
+                &self.heatmap_program,
+                &glium::uniform! {
+                    projection: projection::ortho(bounds),
+                    cell_size: heatmap.cell_size(),
+                    colormap: self.colormap(colormap),
+                },
+                &glium::DrawParameters {
+                    blend: glium::Blend::alpha_blending(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+    }
+
+    pub fn draw_trail<S: Surface>(
+        &self,
+        target: &mut S,
+        segments: &[([f32; 2], [f32; 2], f32)],
+        bounds: (f32, f32, f32, f32),
+    ) {
+        if segments.is_empty() {
+            return;
+        }
+        let mut vertices = Vec::with_capacity(segments.len() * 2);
+        for (from, to, alpha) in segments {
+            vertices.push(TrailVertex {
+                position: *from,
+                alpha: *alpha,
+            });
+            vertices.push(TrailVertex {
+                position: *to,
+                alpha: *alpha,
+            });
+        }
+        let buffer = VertexBuffer::new(&self.context, &vertices).unwrap();
+        target
+            .draw(
+                &buffer,
+                self.line_indices,
+                &self.trail_program,
+                &glium::uniform! { projection: projection::ortho(bounds) },
+                &glium::DrawParameters {
+                    blend: glium::Blend::alpha_blending(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+    }
+
+    pub fn draw_agents<S: Surface>(
+        &mut self,
+        target: &mut S,
+        layer: &LoadedTrajectory,
+        frame_index: usize,
+        bounds: (f32, f32, f32, f32),
+        settings: &Settings,
+        pass: AgentPass,
+    ) {
+        let frame = layer.replay.frame(frame_index);
+        let speeds = layer.replay.speeds(frame_index);
+        let headings = if pass.headings {
+            layer.replay.headings(frame_index)
+        } else {
+            vec![Some(0.0); frame.positions.len()]
+        };
+        self.instances.clear();
+        let step = settings.downsample_step(frame.ids.len());
+        let agents = frame.positions.iter().zip(&speeds).zip(&frame.ids);
+        for (index, (((e, speed), id), heading)) in agents.zip(headings).enumerate() {
+            if *id % step != 0 && pass.selected_id != Some(*id) {
+                continue;
+            }
+            let group = frame.group(index);
+            if !layer.group_visible(group) {
+                continue;
+            }
+            let outside = pass.roi.is_some_and(|roi| !roi.contains(*e));
+            if outside && settings.hide_outside_roi {
+                continue;
+            }
+            let heading = match heading {
+                Some(heading) => heading,
+                None => continue,
+            };
+            self.instances.push(VertexInstanceAttributes {
+                offset: *e,
+                speed: *speed,
+                selected: if pass.selected_id == Some(*id) {
+                    1.0
+                } else {
+                    0.0
+                },
+                dimmed: if outside { 1.0 } else { 0.0 },
+                heading,
+                tint: if pass.group_colors {
+                    layers::group_color(group)
+                } else {
+                    pass.color
+                },
+            })
+        }
+        let count = self.instances.len();
+        if count == 0 {
+            return;
+        }
+        self.reserve_instances(count);
+        let buffer = self.instance_buffer.as_ref().unwrap();
+        let instances = buffer.slice(0..count).unwrap();
+        instances.write(&self.instances);
+        let teleport_speed = if settings.highlight_teleports {
+            settings.teleport_speed
+        } else {
+            f32::MAX
+        };
+        let shape = if pass.headings {
+            &self.marker
+        } else {
+            &self.quad
+        };
+        target
+            .draw(
+                (shape, instances.per_instance().unwrap()),
+                self.indices,
+                &self.agent_program,
+                &glium::uniform! {
+                    projection: projection::ortho(bounds),
+                    radius: settings.agent_radius,
+                    max_speed: settings.max_speed,
+                    teleport_speed: teleport_speed,
+                    colormap: self.colormap(settings.speed_colormap),
+                    alpha: pass.alpha,
+                    solid: pass.solid,
+                    marker: pass.headings,
+                },
+                &glium::DrawParameters {
+                    blend: glium::Blend::alpha_blending(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+    }
+}
+
+// Line segments of a grid covering `bounds`, every `GRID_MAJOR_INTERVAL`th
+// line counted from the origin is emphasized with a higher alpha.
+fn grid_lines(bounds: (f32, f32, f32, f32), spacing: f32) -> Vec<([f32; 2], [f32; 2], f32)> {
+    let (left, right, bottom, top) = bounds;
+    if spacing <= 0.0 || right <= left || top <= bottom {
+        return Vec::new();
+    }
+    let mut spacing = spacing;
+    while f32::max(right - left, top - bottom) / spacing > MAX_GRID_LINES {
+        spacing *= GRID_MAJOR_INTERVAL as f32;
+    }
+    let alpha = |index: i64| {
+        if index % GRID_MAJOR_INTERVAL == 0 {
+            GRID_MAJOR_ALPHA
+        } else {
+            GRID_MINOR_ALPHA
+        }
+    };
+    let mut segments = Vec::new();
+    for index in (left / spacing).ceil() as i64..=(right / spacing).floor() as i64 {
+        let x = index as f32 * spacing;
+        segments.push(([x, bottom], [x, top], alpha(index)));
+    }
+    for index in (bottom / spacing).ceil() as i64..=(top / spacing).floor() as i64 {
+        let y = index as f32 * spacing;
+        segments.push(([left, y], [right, y], alpha(index)));
+    }
+    segments
+}
+
+// Neighbors of `frame_index` within `count` frames together with their
+// alpha, ordered from the most faded to the closest so nearer frames are
+// drawn on top.
+fn onion_frames(
+    frame_index: usize,
+    frames: usize,
+    count: usize,
+    falloff: f32,
+) -> Vec<(usize, f32)> {
+    let mut neighbors = Vec::new();
+    for distance in (1..=count).rev() {
+        let alpha = ONION_ALPHA * falloff.powi(distance as i32 - 1);
+        if distance <= frame_index {
+            neighbors.push((frame_index - distance, alpha));
+        }
+        if frame_index + distance < frames {
+            neighbors.push((frame_index + distance, alpha));
+        }
+    }
+    neighbors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_lines_cover_bounds() {
+        let segments = grid_lines((-0.5, 2.5, 0.0, 1.0), 1.0);
+        let vertical: Vec<f32> = segments
+            .iter()
+            .filter(|(from, to, _)| from[0] == to[0])
+            .map(|(from, _, _)| from[0])
+            .collect();
+        assert_eq!(vertical, vec![0.0, 1.0, 2.0]);
+        assert_eq!(segments.len(), 5);
+        assert_eq!(segments[0].2, GRID_MAJOR_ALPHA);
+        assert_eq!(segments[1].2, GRID_MINOR_ALPHA);
+    }
+
+    #[test]
+    fn onion_frames_fade_and_stay_in_range() {
+        assert!(onion_frames(5, 10, 0, 0.5).is_empty());
+        assert_eq!(
+            onion_frames(1, 3, 2, 0.5),
+            vec![(0, ONION_ALPHA), (2, ONION_ALPHA)]
+        );
+        let frames = onion_frames(5, 10, 2, 0.5);
+        assert_eq!(
+            frames,
+            vec![
+                (3, ONION_ALPHA * 0.5),
+                (7, ONION_ALPHA * 0.5),
+                (4, ONION_ALPHA),
+                (6, ONION_ALPHA)
+            ]
+        );
+    }
+
+    #[test]
+    fn grid_line_count_is_capped() {
+        let segments = grid_lines((-1.0e6, 1.0e6, -1.0e6, 1.0e6), 1.0);
+        assert!(segments.len() as f32 <= 2.0 * (MAX_GRID_LINES + 1.0));
+        assert!(!segments.is_empty());
+    }
+}
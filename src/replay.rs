@@ -1,5 +1,7 @@
+use crate::hotspots;
 use crate::legacy_parsers::Frame;
 use crate::legacy_parsers::Trajectory;
+use crate::rect::Rect;
 use std::cmp;
 use std::time::Duration;
 
@@ -8,42 +10,676 @@ pub struct Replay {
     trajectory: Trajectory,
     pub current_frame_index: usize,
     frame_duration: Duration,
+    // Start time of every frame relative to the first one, taken from the
+    // frame timestamps if all frames have one. Without them frames are
+    // `frame_duration` apart.
+    frame_times: Option<Vec<Duration>>,
     elapsed: Duration,
     total_duration: Duration,
+    max_agent_count: usize,
+    // Number of agents in every frame, as f32 for plotting.
+    agent_counts: Vec<f32>,
+    paused: bool,
+    // First and last frame of the clip playback and seeking are limited to.
+    clip_start: usize,
+    clip_end: usize,
+    // Peak density of every frame, used to find interesting moments.
+    hotspot_scores: Vec<f32>,
+    // The trajectory as loaded while a smoothed copy is shown.
+    raw: Option<Trajectory>,
+    smoothing: usize,
 }
 
 impl Replay {
     pub fn new(trajectory: Trajectory, frame_duration: Duration) -> Self {
         let frame_count = trajectory.frames.len();
-        let total_duration = if frame_count == 0 {
-            Duration::from_secs(0)
-        } else {
-            frame_duration * (frame_count - 1) as u32
+        let frame_times = frame_times(&trajectory.frames);
+        let total_duration = match frame_times.as_ref() {
+            Some(times) => times.last().copied().unwrap_or_default(),
+            None if frame_count == 0 => Duration::from_secs(0),
+            None => frame_duration * (frame_count - 1) as u32,
         };
+        let max_agent_count = trajectory
+            .frames
+            .iter()
+            .map(|f| f.positions.len())
+            .max()
+            .unwrap_or(0);
+        let agent_counts = trajectory
+            .frames
+            .iter()
+            .map(|f| f.positions.len() as f32)
+            .collect();
+        let hotspot_scores = hotspot_scores(&trajectory);
         Self {
             trajectory,
             current_frame_index: 0,
             frame_duration,
+            frame_times,
             elapsed: Duration::from_secs(0),
             total_duration,
+            max_agent_count,
+            agent_counts,
+            paused: false,
+            clip_start: 0,
+            clip_end: frame_count.saturating_sub(1),
+            hotspot_scores,
+            raw: None,
+            smoothing: 0,
         }
     }
 
     pub fn advance_by(&mut self, duration: Duration) {
-        self.elapsed = cmp::min(self.total_duration, self.elapsed + duration);
-        self.current_frame_index =
-            (self.elapsed.as_secs_f64() / self.frame_duration.as_secs_f64()) as usize;
+        if self.paused {
+            return;
+        }
+        let clip_end = self.time_of(self.clip_end);
+        self.elapsed = cmp::min(clip_end, self.elapsed + duration);
+        self.current_frame_index = self.frame_at(self.elapsed);
+    }
+
+    // Jumps to `frame_index`, clamped to the clip.
+    pub fn seek(&mut self, frame_index: usize) {
+        let index = frame_index.clamp(self.clip_start, self.clip_end);
+        self.elapsed = self.time_of(index);
+        self.current_frame_index = index;
+    }
+
+    // Jumps to `time` after the first frame, clamped to the clip. Unlike
+    // `seek` the time can fall between two frames, playback then continues
+    // from there instead of from the start of the frame.
+    pub fn seek_time(&mut self, time: Duration) {
+        let start = self.time_of(self.clip_start);
+        let end = self.time_of(self.clip_end);
+        self.elapsed = time.clamp(start, end);
+        self.current_frame_index = self.frame_at(self.elapsed);
+    }
+
+    // Moves `delta` frames forward or backward, stopping at the first and
+    // last frame of the clip. Playback continues from the new frame once
+    // resumed.
+    pub fn step(&mut self, delta: i64) {
+        let index = (self.current_frame_index as i64 + delta).max(0) as usize;
+        self.seek(index);
+    }
+
+    // Seeks to the next frame after the current one whose peak density is a
+    // local maximum of at least `threshold` times the overall peak.
+    pub fn next_hotspot(&mut self, threshold: f32) -> Option<usize> {
+        let index =
+            hotspots::next_hotspot(&self.hotspot_scores, self.current_frame_index, threshold)?;
+        self.seek(index);
+        Some(self.current_frame_index)
+    }
+
+    pub fn prev_hotspot(&mut self, threshold: f32) -> Option<usize> {
+        let index =
+            hotspots::prev_hotspot(&self.hotspot_scores, self.current_frame_index, threshold)?;
+        self.seek(index);
+        Some(self.current_frame_index)
+    }
+
+    // Parses a frame number typed by the user, without seeking to it.
+    pub fn parse_frame(&self, text: &str) -> Result<usize, String> {
+        let text = text.trim();
+        let frame: usize = text
+            .parse()
+            .map_err(|_| format!("'{}' is not a frame number", text))?;
+        if frame >= self.frames() {
+            return Err(format!(
+                "Frame {} is out of range, the last frame is {}",
+                frame,
+                self.frames().saturating_sub(1)
+            ));
+        }
+        Ok(frame)
+    }
+
+    pub fn clip(&self) -> (usize, usize) {
+        (self.clip_start, self.clip_end)
+    }
+
+    // Limits playback to the frames `start..=end`, moving the current frame
+    // into the clip if it is outside.
+    pub fn set_clip(&mut self, start: usize, end: usize) -> Result<(), String> {
+        if start >= end {
+            return Err(format!(
+                "Clip start {} must be before clip end {}",
+                start, end
+            ));
+        }
+        if end >= self.frames() {
+            return Err(format!(
+                "Clip end {} is past the last frame {}",
+                end,
+                self.frames().saturating_sub(1)
+            ));
+        }
+        self.clip_start = start;
+        self.clip_end = end;
+        self.seek(self.current_frame_index);
+        Ok(())
+    }
+
+    pub fn play(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
     }
 
     pub fn current_frame(&self) -> &Frame {
-        &self.trajectory.frames[self.current_frame_index]
+        self.frame(self.current_frame_index)
+    }
+
+    pub fn frame(&self, frame_index: usize) -> &Frame {
+        &self.trajectory.frames[frame_index]
+    }
+
+    pub fn frame_duration(&self) -> Duration {
+        self.frame_duration
+    }
+
+    // Changes the time between frames, staying on the current frame. Has no
+    // effect on the timing of trajectories with timestamps.
+    pub fn set_frame_duration(&mut self, frame_duration: Duration) {
+        if frame_duration == Duration::from_secs(0) {
+            return;
+        }
+        self.frame_duration = frame_duration;
+        if self.frame_times.is_none() {
+            self.total_duration = frame_duration * self.frames().saturating_sub(1) as u32;
+        }
+        self.elapsed = self.time_of(self.current_frame_index);
+    }
+
+    // Shows positions averaged over `window` frames, see
+    // `Trajectory::smoothed`. A window of 0 or 1 restores the raw data.
+    pub fn set_smoothing(&mut self, window: usize) {
+        let window = if window > 1 { window } else { 0 };
+        if window == self.smoothing {
+            return;
+        }
+        if let Some(raw) = self.raw.take() {
+            self.trajectory = raw;
+        }
+        if window > 0 {
+            let smoothed = self.trajectory.smoothed(window);
+            self.raw = Some(std::mem::replace(&mut self.trajectory, smoothed));
+        }
+        self.smoothing = window;
+        self.hotspot_scores = hotspot_scores(&self.trajectory);
+    }
+
+    // The trajectory as loaded, without smoothing.
+    pub fn raw_trajectory(&self) -> &Trajectory {
+        self.raw.as_ref().unwrap_or(&self.trajectory)
+    }
+
+    pub fn smoothing(&self) -> usize {
+        self.smoothing
+    }
+
+    pub fn has_timestamps(&self) -> bool {
+        self.frame_times.is_some()
+    }
+
+    pub fn current_time(&self) -> Duration {
+        self.elapsed
+    }
+
+    // Time at which `frame_index` is shown, relative to the first frame.
+    pub fn time_of(&self, frame_index: usize) -> Duration {
+        match self.frame_times.as_ref() {
+            Some(times) => times[frame_index],
+            None => self.frame_duration * frame_index as u32,
+        }
+    }
+
+    // Frame shown `time` after the start, the last frame once the
+    // trajectory has ended.
+    pub fn frame_at(&self, time: Duration) -> usize {
+        let index = match self.frame_times.as_ref() {
+            Some(times) => times.partition_point(|t| *t <= time).saturating_sub(1),
+            None => (time.as_nanos() / self.frame_duration.as_nanos().max(1)) as usize,
+        };
+        cmp::min(index, self.frames().saturating_sub(1))
+    }
+
+    // Time between `frame_index` and the frame before it.
+    fn frame_step(&self, frame_index: usize) -> f32 {
+        (self.time_of(frame_index) - self.time_of(frame_index - 1)).as_secs_f32()
+    }
+
+    // Follows the time and play state of `clock`, used to keep the layers
+    // of the scene in step with the first one.
+    pub fn sync_to(&mut self, clock: &Replay) {
+        let time = clock.current_time();
+        self.elapsed = cmp::min(time, self.total_duration);
+        self.current_frame_index = self.frame_at(time);
+        self.paused = clock.paused;
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.total_duration
+    }
+
+    pub fn current_speeds(&self) -> Vec<f32> {
+        self.speeds(self.current_frame_index)
+    }
+
+    // Speed of every agent in the given frame, derived from its position
+    // in the previous frame. Agents without such a predecessor get a
+    // negative speed.
+    pub fn speeds(&self, frame_index: usize) -> Vec<f32> {
+        let frame = self.frame(frame_index);
+        if frame_index == 0 {
+            return vec![-1.0; frame.positions.len()];
+        }
+        let previous = self.frame(frame_index - 1);
+        let frame_duration = self.frame_step(frame_index);
+        frame
+            .positions
+            .iter()
+            .zip(&frame.ids)
+            .map(|(p, id)| match previous.position_of(*id) {
+                Some(q) => {
+                    let dx = p[0] - q[0];
+                    let dy = p[1] - q[1];
+                    (dx * dx + dy * dy).sqrt() / frame_duration
+                }
+                None => -1.0,
+            })
+            .collect()
+    }
+
+    // Direction of motion of every agent in the given frame as an angle in
+    // radians counter-clockwise from the x axis, `None` for agents without
+    // a predecessor or that did not move.
+    pub fn headings(&self, frame_index: usize) -> Vec<Option<f32>> {
+        let frame = self.frame(frame_index);
+        if frame_index == 0 {
+            return vec![None; frame.positions.len()];
+        }
+        let previous = self.frame(frame_index - 1);
+        frame
+            .positions
+            .iter()
+            .zip(&frame.ids)
+            .map(|(p, id)| {
+                let q = previous.position_of(*id)?;
+                let (dx, dy) = (p[0] - q[0], p[1] - q[1]);
+                if dx == 0.0 && dy == 0.0 {
+                    None
+                } else {
+                    Some(dy.atan2(dx))
+                }
+            })
+            .collect()
+    }
+
+    // Speed of a single agent, see `speeds`. `None` if the agent is not
+    // part of the given frame.
+    pub fn speed_of(&self, frame_index: usize, id: u32) -> Option<f32> {
+        let p = self.frame(frame_index).position_of(id)?;
+        if frame_index == 0 {
+            return Some(-1.0);
+        }
+        match self.frame(frame_index - 1).position_of(id) {
+            Some(q) => {
+                let dx = p[0] - q[0];
+                let dy = p[1] - q[1];
+                Some((dx * dx + dy * dy).sqrt() / self.frame_step(frame_index))
+            }
+            None => Some(-1.0),
+        }
+    }
+
+    // Line segments connecting each agent's positions over the `length`
+    // frames leading up to `frame_index`, paired with an alpha value that
+    // fades out towards the past.
+    pub fn trail(&self, frame_index: usize, length: usize) -> Vec<([f32; 2], [f32; 2], f32)> {
+        let mut segments = Vec::new();
+        let oldest = frame_index.saturating_sub(length);
+        for index in (oldest + 1..=frame_index).rev() {
+            let alpha = 1.0 - (frame_index - index) as f32 / length as f32;
+            let from = &self.trajectory.frames[index - 1];
+            let to = &self.trajectory.frames[index];
+            for (q, id) in to.positions.iter().zip(&to.ids) {
+                if let Some(p) = from.position_of(*id) {
+                    segments.push((p, *q, alpha));
+                }
+            }
+        }
+        segments
+    }
+
+    // Complete path of one agent from its first appearance up to
+    // `frame_index`, in the same format as `trail`.
+    pub fn agent_trail(&self, id: u32, frame_index: usize) -> Vec<([f32; 2], [f32; 2], f32)> {
+        let mut segments = Vec::new();
+        let mut previous = None;
+        for frame in &self.trajectory.frames[..=frame_index] {
+            let current = frame.position_of(id);
+            if let (Some(p), Some(q)) = (previous, current) {
+                segments.push((p, q, 1.0));
+            }
+            previous = current;
+        }
+        segments
     }
 
     pub fn area(&self) -> (f32, f32, f32, f32) {
         self.trajectory.area()
     }
 
+    pub fn frame_area(&self, frame_index: usize) -> (f32, f32, f32, f32) {
+        self.trajectory.frame_area(frame_index)
+    }
+
+    pub fn max_agent_count(&self) -> usize {
+        self.max_agent_count
+    }
+
+    pub fn agent_counts(&self) -> &[f32] {
+        &self.agent_counts
+    }
+
+    // Like `agent_counts`, only counting agents within `rect`.
+    pub fn agent_counts_within(&self, rect: &Rect) -> Vec<f32> {
+        self.trajectory
+            .frames
+            .iter()
+            .map(|f| f.positions.iter().filter(|p| rect.contains(**p)).count() as f32)
+            .collect()
+    }
+
     pub fn frames(&self) -> usize {
         self.trajectory.frames.len()
     }
 }
+
+// Renders `duration` as `M:SS.mmm`, minutes are not wrapped into hours.
+pub fn format_timecode(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+fn hotspot_scores(trajectory: &Trajectory) -> Vec<f32> {
+    trajectory
+        .frames
+        .iter()
+        .map(|frame| hotspots::peak_density(frame, hotspots::CELL_SIZE))
+        .collect()
+}
+
+// Frame start times relative to the first frame, `None` unless every frame
+// has a timestamp and they are strictly increasing.
+fn frame_times(frames: &[Frame]) -> Option<Vec<Duration>> {
+    let times = frames
+        .iter()
+        .map(|frame| frame.time)
+        .collect::<Option<Vec<f32>>>()?;
+    let start = *times.first()?;
+    if times.windows(2).any(|pair| pair[1] <= pair[0]) {
+        return None;
+    }
+    Some(
+        times
+            .iter()
+            .map(|t| Duration::from_secs_f32(t - start))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay(frame_count: usize, frame_duration: Duration) -> Replay {
+        let frames = (0..frame_count)
+            .map(|i| Frame {
+                ids: vec![1],
+                positions: vec![[i as f32, 0.0]],
+                time: None,
+                groups: Vec::new(),
+            })
+            .collect();
+        Replay::new(Trajectory::new(frames, None), frame_duration)
+    }
+
+    #[test]
+    fn advance_stops_at_last_frame() {
+        let mut replay = replay(5, Duration::from_millis(100));
+        assert_eq!(replay.total_time(), Duration::from_millis(400));
+        replay.advance_by(Duration::from_millis(250));
+        assert_eq!(replay.current_frame_index, 2);
+        replay.advance_by(Duration::from_secs(10));
+        assert_eq!(replay.current_frame_index, 4);
+        replay.advance_by(Duration::from_millis(100));
+        assert_eq!(replay.current_frame_index, 4);
+    }
+
+    #[test]
+    fn paused_replay_does_not_advance() {
+        let mut replay = replay(5, Duration::from_millis(100));
+        replay.pause();
+        replay.advance_by(Duration::from_millis(250));
+        assert_eq!(replay.current_frame_index, 0);
+        replay.play();
+        replay.advance_by(Duration::from_millis(100));
+        assert_eq!(replay.current_frame_index, 1);
+    }
+
+    #[test]
+    fn single_frame_replay_has_no_duration() {
+        let mut replay = replay(1, Duration::from_millis(100));
+        assert_eq!(replay.total_time(), Duration::from_secs(0));
+        replay.advance_by(Duration::from_secs(1));
+        assert_eq!(replay.current_frame_index, 0);
+    }
+
+    #[test]
+    fn clip_bounds_playback_and_seeking() {
+        let mut replay = replay(10, Duration::from_millis(100));
+        assert_eq!(replay.clip(), (0, 9));
+        assert!(replay.set_clip(5, 5).is_err());
+        assert!(replay.set_clip(6, 3).is_err());
+        assert!(replay.set_clip(2, 10).is_err());
+        replay.set_clip(3, 6).unwrap();
+        assert_eq!(replay.current_frame_index, 3);
+        replay.advance_by(Duration::from_secs(1));
+        assert_eq!(replay.current_frame_index, 6);
+        replay.seek(0);
+        assert_eq!(replay.current_frame_index, 3);
+        replay.step(-1);
+        assert_eq!(replay.current_frame_index, 3);
+        replay.step(10);
+        assert_eq!(replay.current_frame_index, 6);
+    }
+
+    #[test]
+    fn timecodes_are_formatted() {
+        assert_eq!(format_timecode(Duration::from_millis(3250)), "0:03.250");
+        assert_eq!(format_timecode(Duration::from_secs(80)), "1:20.000");
+        assert_eq!(format_timecode(Duration::from_secs(3600)), "60:00.000");
+    }
+
+    #[test]
+    fn step_is_clamped_to_frames() {
+        let mut replay = replay(5, Duration::from_secs_f64(1.0 / 3.0));
+        replay.step(3);
+        assert_eq!(replay.current_frame_index, 3);
+        replay.step(10);
+        assert_eq!(replay.current_frame_index, 4);
+        replay.step(-1);
+        assert_eq!(replay.current_frame_index, 3);
+        replay.step(-10);
+        assert_eq!(replay.current_frame_index, 0);
+    }
+
+    #[test]
+    fn timestamps_map_elapsed_time_to_frames() {
+        let frames = [0.0, 0.1, 0.5, 0.6, 2.0]
+            .iter()
+            .enumerate()
+            .map(|(i, t)| Frame {
+                ids: vec![1],
+                positions: vec![[i as f32, 0.0]],
+                time: Some(10.0 + t),
+                groups: Vec::new(),
+            })
+            .collect();
+        let mut replay = Replay::new(Trajectory::new(frames, None), Duration::from_millis(100));
+        assert_eq!(replay.frame_at(Duration::from_millis(300)), 1);
+        assert_eq!(replay.frame_at(Duration::from_millis(1500)), 3);
+        replay.advance_by(Duration::from_millis(550));
+        assert_eq!(replay.current_frame_index, 2);
+        replay.advance_by(Duration::from_secs(5));
+        assert_eq!(replay.current_frame_index, 4);
+        assert!((replay.total_time().as_secs_f32() - 2.0).abs() < 1e-3);
+        let speed = replay.speed_of(4, 1).unwrap();
+        assert!((speed - 1.0 / 1.4).abs() < 1e-3, "{}", speed);
+    }
+
+    #[test]
+    fn advancing_frame_by_frame_never_skips() {
+        for fps in [3.0, 7.0, 24.0, 29.97, 60.0].iter() {
+            let frame_duration = Duration::from_secs_f64(1.0 / fps);
+            let mut replay = replay(1000, frame_duration);
+            for n in 1..1000 {
+                replay.advance_by(frame_duration);
+                assert_eq!(replay.current_frame_index, n, "fps {}", fps);
+            }
+        }
+    }
+
+    #[test]
+    fn agents_are_counted_per_frame() {
+        let frames = vec![
+            Frame {
+                ids: vec![1, 2],
+                positions: vec![[0.0, 0.0], [3.0, 0.0]],
+                time: None,
+                groups: Vec::new(),
+            },
+            Frame {
+                ids: vec![1],
+                positions: vec![[1.0, 0.0]],
+                time: None,
+                groups: Vec::new(),
+            },
+        ];
+        let replay = Replay::new(Trajectory::new(frames, None), Duration::from_millis(100));
+        assert_eq!(replay.agent_counts(), &[2.0, 1.0]);
+        let rect = Rect::from_corners([-1.0, -1.0], [2.0, 1.0]).unwrap();
+        assert_eq!(replay.agent_counts_within(&rect), vec![1.0, 1.0]);
+    }
+
+    #[test]
+    fn headings_follow_motion() {
+        let frames = vec![
+            Frame {
+                ids: vec![1, 2],
+                positions: vec![[0.0, 0.0], [1.0, 1.0]],
+                time: None,
+                groups: Vec::new(),
+            },
+            Frame {
+                ids: vec![1, 2, 3],
+                positions: vec![[0.0, 1.0], [1.0, 1.0], [5.0, 5.0]],
+                time: None,
+                groups: Vec::new(),
+            },
+        ];
+        let replay = Replay::new(Trajectory::new(frames, None), Duration::from_millis(100));
+        assert_eq!(replay.headings(0), vec![None, None]);
+        let headings = replay.headings(1);
+        assert!((headings[0].unwrap() - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(headings[1..], [None, None]);
+    }
+
+    #[test]
+    fn seek_stays_within_frames() {
+        let mut replay = replay(5, Duration::from_millis(100));
+        replay.seek(4);
+        assert_eq!(replay.current_frame_index, 4);
+        assert_eq!(replay.current_time(), Duration::from_millis(400));
+        replay.seek(0);
+        assert_eq!(replay.current_frame_index, 0);
+        assert_eq!(replay.current_time(), Duration::from_secs(0));
+        replay.seek(100);
+        assert_eq!(replay.current_frame_index, 4);
+        assert_eq!(replay.current_frame().positions, vec![[4.0, 0.0]]);
+        replay.advance_by(Duration::from_millis(10));
+        assert_eq!(replay.current_frame_index, 4);
+        replay.seek(1);
+        replay.advance_by(Duration::from_millis(10));
+        assert_eq!(replay.current_frame_index, 1);
+    }
+
+    #[test]
+    fn seeking_by_time_keeps_the_time_between_frames() {
+        let mut replay = replay(5, Duration::from_millis(100));
+        replay.seek_time(Duration::from_millis(250));
+        assert_eq!(replay.current_frame_index, 2);
+        assert_eq!(replay.current_time(), Duration::from_millis(250));
+        replay.advance_by(Duration::from_millis(60));
+        assert_eq!(replay.current_frame_index, 3);
+        replay.set_clip(1, 3).unwrap();
+        replay.seek_time(Duration::from_secs(10));
+        assert_eq!(replay.current_time(), Duration::from_millis(300));
+        replay.seek_time(Duration::from_secs(0));
+        assert_eq!(replay.current_frame_index, 1);
+    }
+
+    #[test]
+    fn typed_frames_are_validated() {
+        let replay = replay(5, Duration::from_millis(100));
+        assert_eq!(replay.parse_frame(" 3 "), Ok(3));
+        assert_eq!(replay.parse_frame("4"), Ok(4));
+        assert!(replay.parse_frame("5").is_err());
+        assert!(replay.parse_frame("-1").is_err());
+        assert!(replay.parse_frame("abc").is_err());
+        assert!(replay.parse_frame("").is_err());
+    }
+
+    #[test]
+    fn changing_frame_duration_keeps_current_frame() {
+        let mut replay = replay(5, Duration::from_millis(100));
+        replay.seek(2);
+        replay.set_frame_duration(Duration::from_millis(50));
+        assert_eq!(replay.current_frame_index, 2);
+        assert_eq!(replay.current_time(), Duration::from_millis(100));
+        assert_eq!(replay.total_time(), Duration::from_millis(200));
+        replay.advance_by(Duration::from_millis(50));
+        assert_eq!(replay.current_frame_index, 3);
+        replay.set_frame_duration(Duration::from_secs(0));
+        assert_eq!(replay.frame_duration(), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn sync_follows_clock_with_other_frame_rate() {
+        let mut clock = replay(10, Duration::from_millis(100));
+        let mut layer = replay(3, Duration::from_millis(200));
+        clock.seek(3);
+        clock.pause();
+        layer.sync_to(&clock);
+        assert_eq!(layer.current_frame_index, 1);
+        assert!(layer.is_paused());
+        clock.seek(9);
+        layer.sync_to(&clock);
+        assert_eq!(layer.current_frame_index, 2);
+        assert_eq!(layer.current_time(), layer.total_time());
+    }
+}
@@ -0,0 +1,305 @@
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::colormap::Colormap;
+use crate::keybindings::Keybindings;
+use crate::parsers;
+
+const MAX_RECENT_FILES: usize = 10;
+
+// How the scale factor reported by the platform is applied to the UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DpiMode {
+    // Uses the platform scale factor as is.
+    Default,
+    // Rounds the scale factor to whole pixels, keeping fonts crisp.
+    Rounded,
+    // Ignores the platform scale factor.
+    Locked,
+}
+
+impl DpiMode {
+    pub const ALL: [DpiMode; 3] = [DpiMode::Default, DpiMode::Rounded, DpiMode::Locked];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DpiMode::Default => "Platform",
+            DpiMode::Rounded => "Rounded",
+            DpiMode::Locked => "Off",
+        }
+    }
+}
+
+// User preferences that survive restarts. Missing keys fall back to their
+// defaults so config files written by older versions keep loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub background: [f32; 4],
+    // Samples per pixel, 0 disables multisampling. Only read on startup.
+    pub multisampling: u16,
+    // Only read on startup as well. Without vsync `max_fps` caps the frame
+    // rate, 0 leaves it uncapped.
+    pub vsync: bool,
+    pub max_fps: u32,
+    // Multiplies the size of fonts and widgets on top of the platform scale
+    // factor, which is applied according to `dpi_mode` on startup.
+    pub ui_scale: f32,
+    pub dpi_mode: DpiMode,
+    pub agent_radius: f32,
+    // Derives `agent_radius` from the size of the scene whenever a first
+    // trajectory is loaded, cleared once the radius is set by hand.
+    pub auto_agent_radius: bool,
+    // Frames with more agents than `downsample_threshold` only show agents
+    // whose id is a multiple of `downsample_factor`, 0 disables this.
+    pub downsample_threshold: u32,
+    pub downsample_factor: u32,
+    pub playback_speed: f32,
+    // The timeline scrubber lands on whole frames, otherwise it sets the
+    // playback time and can stop between two frames.
+    pub snap_to_frames: bool,
+    // Holding a step key steps repeatedly after `step_repeat_delay` seconds,
+    // speeding up to `step_repeat_rate` frames per second.
+    pub step_repeat_delay: f32,
+    pub step_repeat_rate: f32,
+    pub max_speed: f32,
+    pub speed_colormap: Colormap,
+    // Steps faster than this are considered data errors, reported by the
+    // `validate` command and drawn in red if highlighting is enabled.
+    pub teleport_speed: f32,
+    pub highlight_teleports: bool,
+    pub show_trails: bool,
+    // Markers pointing in the direction each agent moves.
+    pub show_headings: bool,
+    pub trail_length: u32,
+    // Positions are averaged over this many frames, 0 shows the raw data.
+    pub smoothing_window: u32,
+    // Number of frames before and after the current one drawn faded, 0
+    // disables onion-skinning.
+    pub onion_skin_frames: u32,
+    pub onion_skin_falloff: f32,
+    // Differences between two layers shorter than `diff_threshold` are not
+    // drawn, `diff_scale` is the length at the top of the colormap.
+    pub diff_threshold: f32,
+    pub diff_scale: f32,
+    // Length unit shown next to coordinates and distances, which are
+    // multiplied by `unit_scale` first. An empty label uses the unit named in
+    // the trajectory header, if any.
+    pub units: String,
+    pub unit_scale: f32,
+    pub show_grid: bool,
+    pub grid_spacing: f32,
+    pub show_heatmap: bool,
+    pub heatmap_cell_size: f32,
+    pub cumulative_heatmap: bool,
+    pub heatmap_colormap: Colormap,
+    // Fraction of the highest peak density a frame needs to be a hotspot.
+    pub hotspot_threshold: f32,
+    pub fit_view_to_geometry: bool,
+    // Fills the inside of geometry polygons, minus their holes.
+    pub fill_walkable_area: bool,
+    pub walkable_area_color: [f32; 3],
+    pub walkable_area_opacity: f32,
+    pub show_minimap: bool,
+    pub show_agent_count: bool,
+    // The agent count plot only counts agents in the region of interest.
+    pub count_in_roi: bool,
+    // Agents outside the region of interest are hidden instead of dimmed.
+    pub hide_outside_roi: bool,
+    // Margin around the scene after a view reset, relative to its larger
+    // dimension. One agent radius is always added on top.
+    pub view_padding: f32,
+    pub assume_frame_ordered: bool,
+    // Frames per simulation second of Viswalk .fzp files, 0 derives the rate
+    // from the interval between records.
+    pub fzp_fps: f32,
+    // Most recently opened trajectories, newest first.
+    pub recent_files: Vec<PathBuf>,
+    // Kept last, toml requires tables after plain values.
+    pub keybindings: Keybindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self {
+            background: [0.15, 0.15, 0.15, 1.0],
+            multisampling: 4,
+            vsync: true,
+            max_fps: 0,
+            ui_scale: 1.0,
+            dpi_mode: DpiMode::Default,
+            agent_radius: 0.25,
+            auto_agent_radius: true,
+            downsample_threshold: 0,
+            downsample_factor: 4,
+            playback_speed: 1.0,
+            snap_to_frames: true,
+            step_repeat_delay: 0.3,
+            step_repeat_rate: 30.0,
+            max_speed: 1.5,
+            speed_colormap: Colormap::CoolWarm,
+            teleport_speed: 10.0,
+            highlight_teleports: true,
+            show_trails: false,
+            show_headings: false,
+            trail_length: 30,
+            smoothing_window: 0,
+            onion_skin_frames: 0,
+            onion_skin_falloff: 0.6,
+            diff_threshold: 0.05,
+            diff_scale: 1.0,
+            units: String::new(),
+            unit_scale: 1.0,
+            show_grid: false,
+            grid_spacing: 1.0,
+            show_heatmap: false,
+            heatmap_cell_size: 1.0,
+            cumulative_heatmap: false,
+            heatmap_colormap: Colormap::Jet,
+            hotspot_threshold: 0.5,
+            fit_view_to_geometry: true,
+            fill_walkable_area: false,
+            walkable_area_color: [0.35, 0.45, 0.35],
+            walkable_area_opacity: 0.3,
+            show_minimap: true,
+            show_agent_count: false,
+            count_in_roi: false,
+            hide_outside_roi: false,
+            view_padding: 0.05,
+            assume_frame_ordered: false,
+            fzp_fps: 0.0,
+            recent_files: Vec::new(),
+            keybindings: Keybindings::new(),
+        }
+    }
+
+    pub fn add_recent_file(&mut self, path: &Path) {
+        // Stdin can not be opened again.
+        if parsers::is_stdin(path) {
+            return;
+        }
+        self.recent_files.retain(|p| p != path);
+        self.recent_files.insert(0, path.to_path_buf());
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+
+    // Every how many agent ids are drawn in a frame of `agent_count` agents.
+    // Choosing by id keeps the same agents visible from frame to frame.
+    pub fn downsample_step(&self, agent_count: usize) -> u32 {
+        if self.downsample_threshold > 0 && agent_count > self.downsample_threshold as usize {
+            self.downsample_factor.max(1)
+        } else {
+            1
+        }
+    }
+
+    // Location of the config file, `None` if the platform has no notion of
+    // a per user config directory.
+    pub fn path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "VisTwo").map(|dirs| dirs.config_dir().join("settings.toml"))
+    }
+
+    // Window positions and sizes written by imgui, kept next to
+    // the config file.
+    pub fn layout_path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "VisTwo").map(|dirs| dirs.config_dir().join("layout.ini"))
+    }
+
+    // Reads the settings stored at `path`. On first run there is no file
+    // yet, in that case the defaults are written and returned.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| format!("Cannot parse '{}': {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let settings = Self::new();
+                settings.save(path)?;
+                Ok(settings)
+            }
+            Err(e) => Err(format!("Cannot read '{}': {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)
+                .map_err(|e| format!("Cannot create '{}': {}", directory.display(), e))?;
+        }
+        let content = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Cannot write '{}': {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_load_writes_defaults() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("config").join("settings.toml");
+        assert_eq!(Settings::load(&path), Ok(Settings::new()));
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("settings.toml");
+        let mut settings = Settings::new();
+        settings.agent_radius = 0.4;
+        settings.show_trails = true;
+        settings.dpi_mode = DpiMode::Rounded;
+        settings.save(&path).unwrap();
+        assert_eq!(Settings::load(&path), Ok(settings));
+    }
+
+    #[test]
+    fn recent_files_are_deduplicated_and_capped() {
+        let mut settings = Settings::new();
+        for i in 0..12 {
+            settings.add_recent_file(Path::new(&format!("/data/{}.txt", i)));
+        }
+        settings.add_recent_file(Path::new("/data/5.txt"));
+        assert_eq!(settings.recent_files.len(), MAX_RECENT_FILES);
+        assert_eq!(settings.recent_files[0], Path::new("/data/5.txt"));
+        assert_eq!(settings.recent_files[1], Path::new("/data/11.txt"));
+        assert_eq!(
+            settings
+                .recent_files
+                .iter()
+                .filter(|p| *p == Path::new("/data/5.txt"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn downsampling_applies_above_threshold() {
+        let mut settings = Settings::new();
+        assert_eq!(settings.downsample_step(100_000), 1);
+        settings.downsample_threshold = 1000;
+        assert_eq!(settings.downsample_step(1000), 1);
+        assert_eq!(settings.downsample_step(1001), 4);
+        settings.downsample_factor = 0;
+        assert_eq!(settings.downsample_step(1001), 1);
+    }
+
+    #[test]
+    fn corrupt_settings_are_reported() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("settings.toml");
+        std::fs::write(&path, "agent_radius = [").unwrap();
+        assert!(Settings::load(&path).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+use crate::replay::Replay;
+
+// An agent that moved faster than plausible between `frame - 1` and `frame`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Teleport {
+    pub id: u32,
+    pub frame: usize,
+    pub speed: f32,
+}
+
+// All steps faster than `max_speed`, ordered by frame and agent id.
+pub fn find_teleports(replay: &Replay, max_speed: f32) -> Vec<Teleport> {
+    let mut teleports = Vec::new();
+    for frame_index in 1..replay.frames() {
+        let frame = replay.frame(frame_index);
+        let speeds = replay.speeds(frame_index);
+        for (id, speed) in frame.ids.iter().zip(speeds) {
+            if speed > max_speed {
+                teleports.push(Teleport {
+                    id: *id,
+                    frame: frame_index,
+                    speed,
+                });
+            }
+        }
+    }
+    teleports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::legacy_parsers::{Frame, Trajectory};
+    use std::time::Duration;
+
+    #[test]
+    fn fast_steps_are_reported() {
+        let xs = [[0.0, 0.0], [0.1, 5.0], [0.2, 5.1], [0.3, 0.0]];
+        let frames = xs
+            .iter()
+            .map(|x| Frame {
+                ids: vec![1, 2],
+                positions: vec![[x[0], 0.0], [x[1], 0.0]],
+                time: None,
+                groups: Vec::new(),
+            })
+            .collect();
+        let replay = Replay::new(Trajectory::new(frames, None), Duration::from_millis(100));
+        let teleports = find_teleports(&replay, 10.0);
+        let found: Vec<(u32, usize)> = teleports.iter().map(|t| (t.id, t.frame)).collect();
+        assert_eq!(found, vec![(2, 1), (2, 3)]);
+        assert!((teleports[0].speed - 50.0).abs() < 1e-3);
+    }
+}